@@ -15,6 +15,10 @@ const BINARY_START: *mut u8 = BINARY_START_ADDR as *mut u8;
 /// Free space between the bootloader and the loaded binary's start address.
 const MAX_BINARY_SIZE: usize = BOOTLOADER_START_ADDR - BINARY_START_ADDR;
 
+/// The baud rate to receive the binary at. `PI_BAUD` in the kernel's
+/// Makefile must match it.
+const BAUD_RATE: u32 = 115200;
+
 /// Branches to the address `addr` unconditionally.
 fn jump_to(addr: *mut u8) -> ! {
     unsafe {
@@ -29,7 +33,10 @@ pub extern "C" fn kmain() {
     let mut rx_led_on = true;
     rx_led.set();
 
-    let mut uart = pi::uart::MiniUart::new();
+    let mut uart = pi::uart::MiniUart::builder()
+        .baud(BAUD_RATE)
+        .build()
+        .expect("unachievable bootloader baud rate");
     uart.set_read_timeout(750);
 
     let mut buf = unsafe {
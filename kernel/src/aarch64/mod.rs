@@ -0,0 +1,92 @@
+pub mod timer;
+
+/// Returns the current stack pointer.
+#[inline(always)]
+pub fn sp() -> *const u8 {
+    let ptr: usize;
+    unsafe {
+        asm!("mov $0, sp" : "=r"(ptr));
+    }
+
+    ptr as *const u8
+}
+
+/// Returns the current exception level.
+///
+/// # Safety
+/// This function should only be called when EL is >= 1.
+#[inline(always)]
+pub unsafe fn current_el() -> u8 {
+    let el_reg: u64;
+    asm!("mrs $0, CurrentEL" : "=r"(el_reg));
+    ((el_reg & 0b1100) >> 2) as u8
+}
+
+/// Returns the SPSel value.
+#[inline(always)]
+pub fn sp_sel() -> u8 {
+    let ptr: u32;
+    unsafe {
+        asm!("mrs $0, SPSel" : "=r"(ptr));
+    }
+
+    (ptr & 1) as u8
+}
+
+/// Returns the core currently executing. `init.S` copies the core number
+/// from `MPIDR_EL1`, which EL0 can't read, to `TPIDRRO_EL0`, so this may be
+/// called from processes too.
+pub fn affinity() -> usize {
+    let x: usize;
+    unsafe {
+        asm!("mrs $0, TPIDRRO_EL0" : "=r"(x));
+    }
+
+    x
+}
+
+/// Masks IRQs and FIQs on this core and returns the previous value of
+/// `DAIF`, to be passed to `restore_interrupts()`. EL0 may call this too, as
+/// `SCTLR_EL1.UMA` is set by `vm::enable()`.
+#[inline(always)]
+pub fn mask_interrupts() -> u64 {
+    let daif: u64;
+    unsafe {
+        asm!("mrs $0, DAIF
+              msr DAIFSet, #3"
+             : "=r"(daif) :: "memory" : "volatile");
+    }
+
+    daif
+}
+
+/// Restores the interrupt masks saved by `mask_interrupts()`.
+#[inline(always)]
+pub fn restore_interrupts(daif: u64) {
+    unsafe {
+        asm!("msr DAIF, $0" :: "r"(daif) : "memory" : "volatile");
+    }
+}
+
+/// A NOOP that won't be optimized out.
+pub fn nop() {
+    unsafe {
+        asm!("nop" :::: "volatile");
+    }
+}
+
+/// Waits for an interrupt, putting the core in a low-power state until one
+/// is pending. Masked interrupts wake the core too.
+pub fn wfi() {
+    unsafe {
+        asm!("wfi" :::: "volatile");
+    }
+}
+
+/// Waits for an event, putting the core in a low-power state until one
+/// occurs.
+pub fn wfe() {
+    unsafe {
+        asm!("wfe" :::: "volatile");
+    }
+}
@@ -0,0 +1,73 @@
+use std::{cmp, i32};
+
+/// The `ENABLE` bit of `CNTP_CTL_EL0`.
+const CTL_ENABLE: u64 = 1 << 0;
+
+/// The `IMASK` bit of `CNTP_CTL_EL0`, which masks the timer's interrupt.
+const CTL_IMASK: u64 = 1 << 1;
+
+/// The `ISTATUS` bit of `CNTP_CTL_EL0`, set while the timer condition is met.
+const CTL_ISTATUS: u64 = 1 << 2;
+
+/// Returns the frequency of the system counter in Hz.
+pub fn frequency() -> u64 {
+    let frequency: u64;
+    unsafe {
+        asm!("mrs $0, CNTFRQ_EL0" : "=r"(frequency));
+    }
+
+    frequency
+}
+
+/// Returns the value of the system counter, which is shared by all cores.
+pub fn counter() -> u64 {
+    let count: u64;
+    unsafe {
+        // Without the barrier, the counter may be read out of order.
+        asm!("isb
+              mrs $0, CNTPCT_EL0"
+             : "=r"(count) ::: "volatile");
+    }
+
+    count
+}
+
+/// Returns the time since the system counter started, in microseconds.
+pub fn current_time() -> u64 {
+    let (count, frequency) = (counter(), frequency());
+    count / frequency * 1_000_000 + count % frequency * 1_000_000 / frequency
+}
+
+/// Sets up this core's physical timer to fire `us` microseconds from now,
+/// enabling it and unmasking its interrupt. The interrupt is only raised if
+/// it is also enabled in the local interrupt controller. Intervals longer
+/// than the timer can count are shortened to its longest.
+pub fn tick_in(us: u32) {
+    let ticks = us as u64 * frequency() / 1_000_000;
+    let ticks = cmp::min(ticks, i32::MAX as u64);
+    unsafe {
+        asm!("msr CNTP_TVAL_EL0, $0
+              msr CNTP_CTL_EL0, $1
+              isb"
+             :: "r"(ticks), "r"(CTL_ENABLE) :: "volatile");
+    }
+}
+
+/// Disables this core's physical timer. Its interrupt is no longer raised.
+pub fn disable() {
+    unsafe {
+        asm!("msr CNTP_CTL_EL0, $0
+              isb"
+             :: "r"(CTL_IMASK) :: "volatile");
+    }
+}
+
+/// Returns `true` if this core's physical timer is enabled and has fired.
+pub fn has_fired() -> bool {
+    let ctl: u64;
+    unsafe {
+        asm!("mrs $0, CNTP_CTL_EL0" : "=r"(ctl) ::: "volatile");
+    }
+
+    ctl & CTL_ENABLE != 0 && ctl & CTL_ISTATUS != 0
+}
@@ -11,9 +11,11 @@ const BIN_MAX: usize = 29; // 2^29=512M
 const BINS: usize = BIN_MAX + 1;
 
 /// A simple allocator that allocates based on size classes.
+///
+/// Every block is aligned to its size, so a block's buddy is found from its
+/// address alone, whichever region it was carved from.
 pub struct Allocator {
     bins: [LinkedList; BINS],
-    start: usize,
 }
 
 /// Return bin size (2^bin_num)
@@ -25,20 +27,31 @@ impl Allocator {
     /// Creates a new bin allocator that will allocate memory from the region
     /// starting at address `start` and ending at address `end`.
     pub fn new(start: usize, end: usize) -> Allocator {
-        let mut bins = [LinkedList::new(); BINS];
-        let mut current = start;
+        let mut allocator = Allocator {
+            bins: [LinkedList::new(); BINS],
+        };
+        allocator.add_region(start, end);
+        allocator
+    }
+
+    /// Adds the region starting at address `start` and ending at address
+    /// `end` to the memory this allocator hands out. The region is carved
+    /// into the largest blocks aligned to their size that fit; bytes before
+    /// the first 8-byte boundary are unused.
+    pub fn add_region(&mut self, start: usize, end: usize) {
+        let mut current = align_up(start, bin_size(BIN_MIN));
         while current < end {
             match Allocator::max_bin_fits_in_size(end - current) {
                 Some(bin) => {
+                    let bin = min(bin, current.trailing_zeros() as usize);
                     unsafe {
-                        bins[bin].push(current as *mut usize);
+                        self.bins[bin].push(current as *mut usize);
                     }
                     current += bin_size(bin);
                 }
                 None => break,
             }
         }
-        Allocator { bins, start }
     }
 
     fn max_bin_fits_in_size(size: usize) -> Option<usize> {
@@ -137,13 +150,9 @@ impl Allocator {
     }
 
     fn merge_if_buddy_is_empty(&mut self, bin: usize, bin_addr: usize) -> Option<usize> {
-        let buddy_addr = if ((bin_addr - self.start) / bin_size(bin)) % 2 == 0 {
-            // Left bin. Check buddy to the right
-            bin_addr + bin_size(bin)
-        } else {
-            // Right bin. Check buddy to the left
-            bin_addr - bin_size(bin)
-        };
+        // Blocks are aligned to their size: the buddy of a left block is to
+        // its right, and the other way around.
+        let buddy_addr = bin_addr ^ bin_size(bin);
 
         let mut merged = false;
 
@@ -183,9 +192,7 @@ impl Allocator {
 
         let fit_bin = size.trailing_zeros() as usize;
 
-        let distance_to_start = ptr as usize - self.start;
-        let shift_to_bin_start = distance_to_start % bin_size(fit_bin);
-        let bin_addr = ptr as usize - shift_to_bin_start;
+        let bin_addr = align_down(ptr as usize, bin_size(fit_bin));
 
         let mut next_addr = bin_addr;
         for bin in fit_bin..BIN_MAX {
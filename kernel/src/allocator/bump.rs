@@ -19,6 +19,17 @@ impl Allocator {
         }
     }
 
+    /// Adds the region starting at address `start` and ending at address
+    /// `end` to this allocator. A bump allocator can only hand out one
+    /// contiguous region, so it switches to the new region only if it is
+    /// larger than what remains of the current one.
+    pub fn add_region(&mut self, start: usize, end: usize) {
+        if end.saturating_sub(start) > self.end - self.current {
+            self.current = start;
+            self.end = end;
+        }
+    }
+
     /// Allocates memory. Returns a pointer meeting the size and alignment
     /// properties of `layout.size()` and `layout.align()`.
     ///
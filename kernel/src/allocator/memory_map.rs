@@ -0,0 +1,154 @@
+use std::cmp::{max, min};
+
+/// The maximum number of disjoint regions a `MemoryMap` can hold.
+const MAX_REGIONS: usize = 32;
+
+/// A region of physical memory spanning `[start, end)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Region {
+    /// Returns a new region starting at address `start` and ending at (but
+    /// not including) address `end`.
+    pub const fn new(start: usize, end: usize) -> Region {
+        Region { start, end }
+    }
+
+    /// Returns the size of this region in bytes.
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// A set of disjoint, usable physical memory regions sorted by address.
+///
+/// The map is built by first `add`ing every region of RAM the firmware
+/// reports and then `reserve`ing every range that is already in use: the
+/// kernel image, the boot stack, the ATAGs, the peripheral window, and so on.
+/// What remains is handed to the memory allocator.
+///
+/// A `MemoryMap` never allocates; it can be built before the allocator is.
+#[derive(Debug, Copy, Clone)]
+pub struct MemoryMap {
+    regions: [Region; MAX_REGIONS],
+    len: usize,
+}
+
+impl MemoryMap {
+    /// Returns a new, empty memory map.
+    pub const fn new() -> MemoryMap {
+        MemoryMap {
+            regions: [Region { start: 0, end: 0 }; MAX_REGIONS],
+            len: 0,
+        }
+    }
+
+    /// Returns the usable regions in this map sorted by start address.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions[..self.len]
+    }
+
+    /// Returns the total number of usable bytes in this map.
+    pub fn size(&self) -> usize {
+        self.regions().iter().map(|region| region.size()).sum()
+    }
+
+    /// Marks the memory in `[start, end)` as usable. Overlapping and adjacent
+    /// regions are coalesced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` if the map has no room for another region. The map
+    /// is left unmodified in that case.
+    pub fn add(&mut self, start: usize, end: usize) -> Result<(), ()> {
+        if start >= end {
+            return Ok(());
+        }
+
+        let old = *self;
+        let (mut start, mut end) = (start, end);
+        let mut inserted = false;
+
+        self.len = 0;
+        for region in old.regions() {
+            let result = if region.end < start {
+                self.push(*region)
+            } else if region.start > end {
+                if !inserted {
+                    inserted = true;
+                    self.push(Region::new(start, end))
+                        .and_then(|_| self.push(*region))
+                } else {
+                    self.push(*region)
+                }
+            } else {
+                start = min(start, region.start);
+                end = max(end, region.end);
+                Ok(())
+            };
+
+            if result.is_err() {
+                *self = old;
+                return Err(());
+            }
+        }
+
+        if !inserted && self.push(Region::new(start, end)).is_err() {
+            *self = old;
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    /// Removes the memory in `[start, end)` from the usable regions, trimming
+    /// or splitting any region it overlaps.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` if splitting a region would exceed the capacity of
+    /// the map. The map is left unmodified in that case.
+    pub fn reserve(&mut self, start: usize, end: usize) -> Result<(), ()> {
+        if start >= end {
+            return Ok(());
+        }
+
+        let old = *self;
+        self.len = 0;
+        for region in old.regions() {
+            if end <= region.start || start >= region.end {
+                if self.push(*region).is_err() {
+                    *self = old;
+                    return Err(());
+                }
+                continue;
+            }
+
+            if region.start < start && self.push(Region::new(region.start, start)).is_err() {
+                *self = old;
+                return Err(());
+            }
+
+            if end < region.end && self.push(Region::new(end, region.end)).is_err() {
+                *self = old;
+                return Err(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends `region` to the end of the map.
+    fn push(&mut self, region: Region) -> Result<(), ()> {
+        if self.len == MAX_REGIONS {
+            return Err(());
+        }
+
+        self.regions[self.len] = region;
+        self.len += 1;
+        Ok(())
+    }
+}
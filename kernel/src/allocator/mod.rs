@@ -1,4 +1,5 @@
 mod linked_list;
+mod memory_map;
 mod util;
 
 #[path = "bin.rs"]
@@ -8,12 +9,16 @@ mod imp;
 mod tests;
 
 use alloc::heap::{Alloc, AllocErr, Layout};
-use mutex::Mutex;
-// use std::cmp::max;
+use boot;
+use mutex::IrqMutex;
+use pi::common::{IO_BASE, IO_END};
+use smp;
+
+pub use self::memory_map::{MemoryMap, Region};
 
 /// Thread-safe (locking) wrapper around a particular memory allocator.
 #[derive(Debug)]
-pub struct Allocator(Mutex<Option<imp::Allocator>>);
+pub struct Allocator(IrqMutex<Option<imp::Allocator>>);
 
 impl Allocator {
     /// Returns an uninitialized `Allocator`.
@@ -21,7 +26,7 @@ impl Allocator {
     /// The allocator must be initialized by calling `initialize()` before the
     /// first memory allocation. Failure to do will result in panics.
     pub const fn uninitialized() -> Self {
-        Allocator(Mutex::new(None))
+        Allocator(IrqMutex::new(None))
     }
 
     /// Initializes the memory allocator.
@@ -30,8 +35,16 @@ impl Allocator {
     ///
     /// Panics if the system's memory map could not be retrieved.
     pub fn initialize(&self) {
-        let (start, end) = memory_map().expect("failed to find memory map");
-        *self.0.lock() = Some(imp::Allocator::new(start, end));
+        let map = memory_map().expect("failed to find memory map");
+        let mut regions = map.regions().iter();
+        let first = regions.next().expect("no usable memory found");
+
+        let mut allocator = imp::Allocator::new(first.start, first.end);
+        for region in regions {
+            allocator.add_region(region.start, region.end);
+        }
+
+        *self.0.lock() = Some(allocator);
     }
 }
 
@@ -87,23 +100,36 @@ unsafe impl<'a> Alloc for &'a Allocator {
 }
 
 extern "C" {
+    static _start: u8;
     static _end: u8;
 }
 
-use pi::atags::Atags;
+/// The size of the cores' stacks, which grow down from `_start` one after
+/// the other.
+const BOOT_STACK_SIZE: usize = smp::STACK_SIZE * smp::NUM_CORES;
 
-/// Returns the (start address, end address) of the available memory on this
-/// system if it can be determined. If it cannot, `None` is returned.
+/// Returns the usable memory on this system if it can be determined. If it
+/// cannot, `None` is returned.
+///
+/// The boot information, either ATAGs or a device tree, describes the RAM and
+/// reserves the memory it occupies itself. The firmware only reports memory
+/// the ARM owns, so the VideoCore's share of the GPU split is never included.
+/// From what remains we subtract the boot stack, the kernel image, the
+/// peripheral window, and the initrd.
 ///
 /// This function is expected to return `Some` under all normal cirumstances.
-fn memory_map() -> Option<(usize, usize)> {
-    let binary_end = unsafe { (&_end as *const u8) as u32 };
+fn memory_map() -> Option<MemoryMap> {
+    let (kernel_start, kernel_end) = unsafe {
+        (&_start as *const u8 as usize, &_end as *const u8 as usize)
+    };
 
-    for atag in Atags::get() {
-        if let Some(mem) = atag.mem() {
-            return Some((binary_end as usize, (mem.start + mem.size) as usize));
-        }
+    let mut map = boot::info().memory_map()?;
+    map.reserve(kernel_start.saturating_sub(BOOT_STACK_SIZE), kernel_start).ok()?;
+    map.reserve(kernel_start, kernel_end).ok()?;
+    map.reserve(IO_BASE, IO_END).ok()?;
+    if let Some((start, size)) = boot::info().initrd() {
+        map.reserve(start, start + size).ok()?;
     }
 
-    None
+    Some(map)
 }
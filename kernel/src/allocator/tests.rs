@@ -287,6 +287,56 @@ mod allocator {
             }
         }
     });
+
+    #[test]
+    fn bin_dealloc_regions() {
+        let mem: RawVec<u8> = RawVec::with_capacity(1 << 16);
+        let base = mem.ptr() as usize;
+
+        // Two regions separated by a gap, neither 8-byte aligned, the second
+        // added after the allocator was created from the first.
+        let regions = [(base + 3, base + 0x3005), (base + 0x4004, base + 0xFFF9)];
+        let mut a = bin::Allocator::new(regions[0].0, regions[0].1);
+        a.add_region(regions[1].0, regions[1].1);
+
+        let layouts = [
+            layout!(16, 16),
+            layout!(24, 8),
+            layout!(512, 8),
+            layout!(1024, 1024),
+            layout!(4096, 8),
+        ];
+
+        // Freed blocks must go back to their own bins and merge only with
+        // their buddies, or later allocations land in the gap or overlap.
+        for _ in 0..100 {
+            let mut pointers: Vec<(usize, Layout)> = vec![];
+            for layout in &layouts {
+                let ptr = a.alloc(layout.clone()).expect("allocation") as usize;
+                assert!(
+                    regions.iter().any(|&(start, end)| ptr >= start && ptr + layout.size() <= end),
+                    "{:x} + {:x} is outside of the regions",
+                    ptr,
+                    layout.size()
+                );
+                assert!(ptr % layout.align() == 0,
+                    "{:x} is not aligned to {}", ptr, layout.align());
+                scribble(ptr as *mut u8, layout.size());
+                pointers.push((ptr, layout.clone()));
+            }
+
+            pointers.sort_by_key(|&(ptr, _)| ptr);
+            for window in pointers.windows(2) {
+                let (&(ptr_a, ref layout_a), &(ptr_b, _)) = (&window[0], &window[1]);
+                assert!(ptr_b - ptr_a >= layout_a.size(),
+                    "memory region {:x} - {:x} does not fit {}", ptr_a, ptr_b, layout_a.size());
+            }
+
+            for (ptr, layout) in pointers {
+                a.dealloc(ptr as *mut u8, layout);
+            }
+        }
+    }
 }
 
 mod linked_list {
@@ -381,3 +431,76 @@ mod linked_list {
         assert_eq!(iter.next(), None);
     }
 }
+
+mod memory_map {
+    use allocator::memory_map::{MemoryMap, Region};
+
+    #[test]
+    fn add_coalesces() {
+        let mut map = MemoryMap::new();
+        map.add(0x1000, 0x2000).unwrap();
+        map.add(0x4000, 0x5000).unwrap();
+        map.add(0x2000, 0x3000).unwrap();
+        map.add(0x0, 0x0).unwrap();
+
+        assert_eq!(
+            map.regions(),
+            &[Region::new(0x1000, 0x3000), Region::new(0x4000, 0x5000)]
+        );
+
+        map.add(0x2800, 0x4800).unwrap();
+        assert_eq!(map.regions(), &[Region::new(0x1000, 0x5000)]);
+        assert_eq!(map.size(), 0x4000);
+    }
+
+    #[test]
+    fn add_sorts() {
+        let mut map = MemoryMap::new();
+        map.add(0x8000, 0x9000).unwrap();
+        map.add(0x1000, 0x2000).unwrap();
+        map.add(0x4000, 0x5000).unwrap();
+
+        assert_eq!(
+            map.regions(),
+            &[
+                Region::new(0x1000, 0x2000),
+                Region::new(0x4000, 0x5000),
+                Region::new(0x8000, 0x9000),
+            ]
+        );
+    }
+
+    #[test]
+    fn reserve_trims_and_splits() {
+        let mut map = MemoryMap::new();
+        map.add(0x0, 0x10000).unwrap();
+        map.add(0x20000, 0x30000).unwrap();
+
+        // Trim the front, split the middle, and remove a region entirely.
+        map.reserve(0x0, 0x100).unwrap();
+        map.reserve(0x8000, 0x9000).unwrap();
+        map.reserve(0x1F000, 0x31000).unwrap();
+
+        assert_eq!(
+            map.regions(),
+            &[Region::new(0x100, 0x8000), Region::new(0x9000, 0x10000)]
+        );
+
+        // Reserving memory that isn't in the map is a no-op.
+        map.reserve(0x40000, 0x50000).unwrap();
+        assert_eq!(map.size(), 0x7F00 + 0x7000);
+    }
+
+    #[test]
+    fn full_map_is_unmodified() {
+        let mut map = MemoryMap::new();
+        let mut i = 0;
+        while map.add(i * 0x2000, i * 0x2000 + 0x1000).is_ok() {
+            i += 1;
+        }
+
+        let before = map.regions().to_vec();
+        assert!(map.reserve(0x100, 0x200).is_err());
+        assert_eq!(map.regions(), &before[..]);
+    }
+}
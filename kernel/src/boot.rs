@@ -0,0 +1,165 @@
+use pi::atags::Atags;
+use pi::fdt::Fdt;
+
+use allocator::MemoryMap;
+use fs::initrd;
+use mutex::Mutex;
+
+/// The end of the firmware's spin tables at the bottom of RAM. The ATAGs, if
+/// any, start right after them.
+pub const SPIN_TABLE_END: usize = 0x100;
+
+/// The form of the boot information the firmware handed to the kernel.
+#[derive(Debug, Copy, Clone)]
+enum Source {
+    /// A list of ATAGs at the fixed ATAG address.
+    Atags,
+    /// A flattened device tree.
+    Fdt(Fdt),
+}
+
+/// The boot information the firmware handed to the kernel.
+#[derive(Debug, Copy, Clone)]
+pub struct BootInfo {
+    source: Source,
+    /// The `(address, size)` of the initial RAM disk, once located.
+    initrd: Option<(usize, usize)>,
+}
+
+impl BootInfo {
+    /// Determines what kind of boot information is at `addr`, the value of
+    /// `x0` when the kernel was entered. If `addr` doesn't point to a device
+    /// tree, the firmware is assumed to have passed ATAGs.
+    ///
+    /// The initrd isn't located yet; see `initialize()`.
+    pub fn from_addr(addr: usize) -> BootInfo {
+        let source = match unsafe { Fdt::from_addr(addr) } {
+            Some(fdt) => Source::Fdt(fdt),
+            None => Source::Atags,
+        };
+
+        BootInfo { source, initrd: None }
+    }
+
+    /// Returns a memory map containing the system's RAM with the memory
+    /// occupied by the boot information itself already reserved. Returns
+    /// `None` if no RAM is described or the map overflows.
+    pub fn memory_map(&self) -> Option<MemoryMap> {
+        let mut map = MemoryMap::new();
+
+        match self.source {
+            Source::Atags => {
+                for atag in Atags::get() {
+                    if let Some(mem) = atag.mem() {
+                        let start = mem.start as usize;
+                        map.add(start, start + mem.size as usize).ok()?;
+                    }
+                }
+
+                map.reserve(0, Atags::get().end()).ok()?;
+            }
+            Source::Fdt(fdt) => {
+                for (start, size) in fdt.memory()? {
+                    map.add(start as usize, (start + size) as usize).ok()?;
+                }
+
+                map.reserve(0, SPIN_TABLE_END).ok()?;
+                map.reserve(fdt.addr(), fdt.addr() + fdt.size()).ok()?;
+                for (start, size) in fdt.reservations() {
+                    map.reserve(start as usize, (start + size) as usize).ok()?;
+                }
+
+                // The drivers in `pi` address the peripherals at the fixed
+                // `IO_BASE` of the BCM2837, so the window the tree describes
+                // is deliberately only used to keep RAM allocations out of it.
+                if let Some((base, size)) = fdt.peripherals() {
+                    map.reserve(base, base + size).ok()?;
+                }
+            }
+        }
+
+        match map.size() {
+            0 => None,
+            _ => Some(map),
+        }
+    }
+
+    /// Returns the kernel command line, if the firmware passed one.
+    pub fn cmdline(&self) -> Option<&'static str> {
+        match self.source {
+            Source::Atags => Atags::get().filter_map(|atag| atag.cmd()).next(),
+            Source::Fdt(fdt) => fdt.bootargs(),
+        }
+    }
+
+    /// Returns the `(address, size)` of the initial RAM disk, if there is
+    /// one. It is located once by `initialize()`.
+    pub fn initrd(&self) -> Option<(usize, usize)> {
+        self.initrd
+    }
+
+    /// Returns the `(address, size)` of the initial RAM disk the firmware or
+    /// bootloader loaded, if any.
+    fn loaded_initrd(&self) -> Option<(usize, usize)> {
+        match self.source {
+            Source::Atags => Atags::get()
+                .filter_map(|atag| atag.initrd())
+                .next()
+                .map(|initrd| (initrd.start as usize, initrd.size as usize)),
+            Source::Fdt(fdt) => {
+                let chosen = fdt.find("/chosen")?;
+                let start = chosen.property("linux,initrd-start")?.as_u64()? as usize;
+                let end = chosen.property("linux,initrd-end")?.as_u64()? as usize;
+                Some((start, end.checked_sub(start)?))
+            }
+        }
+    }
+
+    /// Returns the board's serial number, if the firmware passed one.
+    pub fn serial(&self) -> Option<u64> {
+        match self.source {
+            Source::Atags => Atags::get()
+                .filter_map(|atag| atag.serial())
+                .next()
+                .map(|serial| (serial.high as u64) << 32 | serial.low as u64),
+            Source::Fdt(fdt) => fdt.find("/system")?.property("linux,serial")?.as_u64(),
+        }
+    }
+
+    /// Returns the board's revision code, if the firmware passed one.
+    pub fn revision(&self) -> Option<u32> {
+        match self.source {
+            Source::Atags => Atags::get()
+                .filter_map(|atag| atag.revision())
+                .next()
+                .map(|revision| revision.rev),
+            Source::Fdt(fdt) => fdt.find("/system")?.property("linux,revision")?.as_u32(),
+        }
+    }
+}
+
+/// The boot information, set once by `initialize()`.
+static BOOT_INFO: Mutex<Option<BootInfo>> = Mutex::new(None);
+
+/// Records the boot information at `addr`, the value of `x0` when the
+/// kernel was entered. Must be called before anything calls `info()`.
+///
+/// The initrd is located here too: one the firmware names takes precedence
+/// over an archive appended to the kernel image. This must happen before the
+/// allocator is initialized, as the heap starts right after the image, where
+/// the appended archive is.
+pub fn initialize(addr: usize) {
+    let mut info = BootInfo::from_addr(addr);
+    info.initrd = info.loaded_initrd()
+        .or_else(|| info.memory_map().and_then(|map| initrd::find_appended(&map)));
+    *BOOT_INFO.lock() = Some(info);
+}
+
+/// Returns the boot information recorded by `initialize()`.
+///
+/// # Panics
+///
+/// Panics if `initialize()` has not been called.
+pub fn info() -> BootInfo {
+    (*BOOT_INFO.lock()).expect("boot info uninitialized")
+}
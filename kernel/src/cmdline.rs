@@ -0,0 +1,274 @@
+use std::fmt;
+use std::str::SplitWhitespace;
+
+use console::{kprintln, Device, LogLevel};
+use process::TICK;
+
+/// Where the root file system comes from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Root {
+    /// The first FAT32 partition on the SD card.
+    Sd,
+    /// The initial RAM disk.
+    Initrd,
+    /// No root file system.
+    None,
+}
+
+/// Boot parameters read from the kernel command line.
+///
+/// The command line is a whitespace-separated list of `key=value` pairs.
+/// Keys the kernel doesn't know, such as those the firmware adds for Linux,
+/// are ignored. The following keys are understood:
+///
+///   * `root=sd|initrd|none`: where to mount the root file system from.
+///     Linux-style `/dev/mmcblk0pN` paths select the SD card and `/dev/ram0`
+///     the initrd. If the SD card fails to mount, the initrd is used instead.
+///   * `console=ttyS0|ttyAMA0[,N]`: the UART the console is attached to,
+///     named as Linux does: `ttyS0` (or `serial0`) is the mini UART and
+///     `ttyAMA0` (or `serial1`) the PL011. A baud rate may follow the comma.
+///   * `console=tty0|tty1`: also mirror the console to the screen. This may
+///     be given in addition to a UART console, as on Linux.
+///   * `baud=N`: the console's baud rate.
+///   * `loglevel=error|warn|info|debug|trace`: the most verbose kernel log
+///     messages to print. The levels may also be given as `0` through `4`.
+///   * `tick=N`: the scheduler's time slice in milliseconds.
+///   * `shell=yes|no`: whether to start the shell after booting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Params {
+    pub root: Root,
+    pub console: Device,
+    /// Whether console output is mirrored to the screen.
+    pub screen: bool,
+    pub baud: Option<u32>,
+    pub log_level: LogLevel,
+    /// The scheduler's time slice in microseconds.
+    pub tick: u32,
+    pub shell: bool,
+}
+
+/// Error type for command line options with invalid values.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<'a> {
+    /// A known option was given without a value.
+    MissingValue(&'a str),
+    /// A known option was given a value it can't take.
+    InvalidValue(&'a str, &'a str),
+}
+
+impl<'a> fmt::Display for Error<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingValue(key) => write!(f, "missing value for `{}`", key),
+            Error::InvalidValue(key, value) => {
+                write!(f, "invalid value `{}` for `{}`", value, key)
+            }
+        }
+    }
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            root: Root::Sd,
+            console: Device::MiniUart,
+            screen: false,
+            baud: None,
+            log_level: LogLevel::Debug,
+            tick: TICK,
+            shell: true,
+        }
+    }
+}
+
+impl Params {
+    /// Parses `cmdline`, starting from the default parameters. Options with
+    /// invalid values are reported on the console and keep their defaults.
+    pub fn parse(cmdline: &str) -> Params {
+        let mut params = Params::default();
+        for (key, value) in pairs(cmdline) {
+            if let Err(e) = params.set(key, value) {
+                kprintln!("cmdline: {}", e);
+            }
+        }
+
+        params
+    }
+
+    /// Sets the option `key` to `value`. Unknown keys are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MissingValue` if `key` is known but `value` is `None`
+    /// and `Error::InvalidValue` if `value` is not valid for `key`. `self` is
+    /// not modified on error.
+    pub fn set<'a>(&mut self, key: &'a str, value: Option<&'a str>) -> Result<(), Error<'a>> {
+        match key {
+            "root" | "console" | "baud" | "loglevel" | "tick" | "shell" => {}
+            _ => return Ok(()),
+        }
+
+        let value = value.ok_or(Error::MissingValue(key))?;
+        let invalid = Error::InvalidValue(key, value);
+        match key {
+            "root" => {
+                self.root = match value {
+                    "sd" => Root::Sd,
+                    "initrd" | "/dev/ram0" => Root::Initrd,
+                    "none" => Root::None,
+                    _ if value.starts_with("/dev/mmcblk0p") => Root::Sd,
+                    _ => return Err(invalid),
+                }
+            }
+            "console" => {
+                let (device, options) = match value.find(',') {
+                    Some(i) => (&value[..i], Some(&value[i + 1..])),
+                    None => (value, None),
+                };
+
+                let device = match device {
+                    "ttyS0" | "serial0" => Device::MiniUart,
+                    "ttyAMA0" | "serial1" => Device::Pl011,
+                    "tty0" | "tty1" => {
+                        self.screen = true;
+                        return Ok(());
+                    }
+                    _ => return Ok(()),
+                };
+
+                // Linux-style options are the baud rate followed by the
+                // parity and data bits, as in `115200n8`.
+                let baud = match options {
+                    Some(options) => {
+                        let digits = options.find(|c: char| !c.is_digit(10));
+                        let baud = &options[..digits.unwrap_or(options.len())];
+                        match baud.parse::<u32>() {
+                            Ok(0) | Err(_) => return Err(invalid),
+                            Ok(baud) => Some(baud),
+                        }
+                    }
+                    None => None,
+                };
+
+                self.console = device;
+                self.baud = baud.or(self.baud);
+            }
+            "baud" => match value.parse::<u32>() {
+                Ok(0) | Err(_) => return Err(invalid),
+                Ok(baud) => self.baud = Some(baud),
+            },
+            "loglevel" => {
+                self.log_level = match value {
+                    "error" | "0" => LogLevel::Error,
+                    "warn" | "1" => LogLevel::Warn,
+                    "info" | "2" => LogLevel::Info,
+                    "debug" | "3" => LogLevel::Debug,
+                    "trace" | "4" => LogLevel::Trace,
+                    _ => return Err(invalid),
+                }
+            }
+            "tick" => match value.parse::<u32>().ok().and_then(|ms| ms.checked_mul(1000)) {
+                Some(0) | None => return Err(invalid),
+                Some(us) => self.tick = us,
+            },
+            "shell" => {
+                self.shell = match value {
+                    "yes" | "on" | "true" | "1" => true,
+                    "no" | "off" | "false" | "0" => false,
+                    _ => return Err(invalid),
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns an iterator over the options in `cmdline`. Each item is a key and
+/// its value, if it has one: `a=b c` yields `("a", Some("b"))` and
+/// `("c", None)`.
+pub fn pairs(cmdline: &str) -> Pairs {
+    Pairs(cmdline.split_whitespace())
+}
+
+/// An iterator over the `key=value` pairs of a command line.
+pub struct Pairs<'a>(SplitWhitespace<'a>);
+
+impl<'a> Iterator for Pairs<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let word = self.0.next()?;
+        match word.find('=') {
+            Some(i) => Some((&word[..i], Some(&word[i + 1..]))),
+            None => Some((word, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_pairs() {
+        let mut iter = pairs("  a=b c  d= e=f=g ");
+        assert_eq!(iter.next(), Some(("a", Some("b"))));
+        assert_eq!(iter.next(), Some(("c", None)));
+        assert_eq!(iter.next(), Some(("d", Some(""))));
+        assert_eq!(iter.next(), Some(("e", Some("f=g"))));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn check_parse() {
+        let params = Params::parse(
+            "console=ttyS0,115200 root=none baud=230400 loglevel=warn tick=10 shell=no",
+        );
+
+        assert_eq!(params.root, Root::None);
+        assert_eq!(params.console, Device::MiniUart);
+        assert_eq!(params.baud, Some(230400));
+        assert_eq!(params.log_level, LogLevel::Warn);
+        assert_eq!(params.tick, 10 * 1000);
+        assert_eq!(params.shell, false);
+
+        assert_eq!(Params::parse(""), Params::default());
+    }
+
+    #[test]
+    fn check_invalid() {
+        let mut params = Params::default();
+        assert_eq!(params.set("baud", Some("fast")), Err(Error::InvalidValue("baud", "fast")));
+        assert_eq!(params.set("tick", Some("0")), Err(Error::InvalidValue("tick", "0")));
+        assert_eq!(params.set("shell", None), Err(Error::MissingValue("shell")));
+        assert_eq!(params.set("quiet", None), Ok(()));
+        assert_eq!(params, Params::default());
+
+        assert_eq!(params.set("root", Some("/dev/mmcblk0p2")), Ok(()));
+        assert_eq!(params.root, Root::Sd);
+        assert_eq!(params.set("root", Some("initrd")), Ok(()));
+        assert_eq!(params.root, Root::Initrd);
+    }
+
+    #[test]
+    fn check_console() {
+        let params = Params::parse("console=tty1 console=ttyAMA0,9600n8");
+        assert_eq!(params.console, Device::Pl011);
+        assert_eq!(params.screen, true);
+        assert_eq!(params.baud, Some(9600));
+
+        let params = Params::parse("console=serial0 console=tty2");
+        assert_eq!(params.console, Device::MiniUart);
+        assert_eq!(params.screen, false);
+        assert_eq!(params.baud, None);
+
+        let mut params = Params::default();
+        assert_eq!(
+            params.set("console", Some("ttyAMA0,fast")),
+            Err(Error::InvalidValue("console", "ttyAMA0,fast"))
+        );
+        assert_eq!(params, Params::default());
+    }
+}
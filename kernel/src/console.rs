@@ -1,80 +1,207 @@
 use std::fmt;
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use pi::interrupt::Interrupt;
+use pi::pl011::Pl011;
 use pi::uart::MiniUart;
 
-use mutex::Mutex;
+use mutex::IrqMutex;
+use syscall;
+use traps;
+
+/// A UART the console can be attached to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Device {
+    /// The mini UART, `ttyS0` to Linux.
+    MiniUart,
+    /// The PL011 UART, `ttyAMA0` to Linux.
+    Pl011,
+}
+
+/// An initialized UART.
+enum Uart {
+    Mini(MiniUart),
+    Pl011(Pl011),
+}
+
+/// Evaluates `$body` with `$uart` bound to the console's initialized UART,
+/// whichever kind it is.
+macro_rules! with_uart {
+    ($console:expr, $uart:ident => $body:expr) => {
+        match *$console.inner() {
+            Uart::Mini(ref mut $uart) => $body,
+            Uart::Pl011(ref mut $uart) => $body,
+        }
+    };
+}
 
 /// A global singleton allowing read/write access to the console.
 pub struct Console {
-    inner: Option<MiniUart>,
+    device: Device,
+    inner: Option<Uart>,
 }
 
 impl Console {
     /// Creates a new instance of `Console`.
     const fn new() -> Console {
-        Console { inner: None }
+        Console {
+            device: Device::MiniUart,
+            inner: None,
+        }
     }
 
     /// Initializes the console if it's not already initialized.
     #[inline]
     fn initialize(&mut self) {
         if self.inner.is_none() {
-            self.inner = Some(MiniUart::new());
+            self.inner = Some(match self.device {
+                Device::MiniUart => Uart::Mini(MiniUart::new()),
+                Device::Pl011 => Uart::Pl011(Pl011::new()),
+            });
         }
     }
 
-    /// Returns a mutable borrow to the inner `MiniUart`, initializing it as
-    /// needed.
-    fn inner(&mut self) -> &mut MiniUart {
+    /// Returns a mutable borrow to the inner UART, initializing it as needed.
+    fn inner(&mut self) -> &mut Uart {
         self.initialize();
         self.inner.as_mut().unwrap()
     }
 
+    /// Attaches the console to `device`. The UART is initialized on next
+    /// use; call this before `set_baud_rate()` and `enable_interrupts()`.
+    pub fn select(&mut self, device: Device) {
+        if device != self.device {
+            if self.inner.is_some() {
+                self.flush();
+            }
+
+            self.device = device;
+            self.inner = None;
+        }
+    }
+
     /// Reads a byte from the UART device, blocking until a byte is available.
+    ///
+    /// This busy-waits while holding the console. Processes should use the
+    /// free function `read_byte()` instead.
     pub fn read_byte(&mut self) -> u8 {
-        self.inner().read_byte()
+        with_uart!(self, uart => uart.read_byte())
+    }
+
+    /// Reads a byte from the UART device if one is available. Never blocks.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        with_uart!(self, uart => uart.try_read_byte())
+    }
+
+    /// Returns `true` if there is a byte ready to be read.
+    pub fn has_byte(&mut self) -> bool {
+        with_uart!(self, uart => uart.has_byte())
+    }
+
+    /// Switches the UART device to interrupt-driven, buffered I/O and
+    /// registers an IRQ handler that calls `poll()` for its interrupt.
+    pub fn enable_interrupts(&mut self) {
+        let interrupt = match *self.inner() {
+            Uart::Mini(ref mut uart) => {
+                uart.enable_interrupts();
+                Interrupt::Aux
+            }
+            Uart::Pl011(ref mut uart) => {
+                uart.enable_interrupts();
+                Interrupt::Uart
+            }
+        };
+
+        traps::irq::register(interrupt, |_, _| CONSOLE.lock().poll());
+    }
+
+    /// Services the UART device: buffers received bytes and sends buffered
+    /// ones.
+    pub fn poll(&mut self) {
+        with_uart!(self, uart => uart.poll())
     }
 
     /// Writes the byte `byte` to the UART device.
     pub fn write_byte(&mut self, byte: u8) {
-        self.inner().write_byte(byte);
+        with_uart!(self, uart => uart.write_byte(byte))
+    }
+
+    /// Blocks until all buffered output has been sent.
+    pub fn flush(&mut self) {
+        with_uart!(self, uart => uart.flush())
+    }
+
+    /// Sets the baud rate of the UART device to `baud`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` if the UART device can't run at `baud`. The baud
+    /// rate is then unchanged.
+    pub fn set_baud_rate(&mut self, baud: u32) -> Result<(), ()> {
+        match *self.inner() {
+            Uart::Mini(ref mut uart) => uart.set_baud_rate(baud).map_err(|_| ()),
+            Uart::Pl011(ref mut uart) => uart.set_baud_rate(baud).map_err(|_| ()),
+        }
     }
 }
 
 impl io::Read for Console {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner().read(buf)
+        with_uart!(self, uart => uart.read(buf))
     }
 }
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner().write(buf)
+        with_uart!(self, uart => uart.write(buf))
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        Console::flush(self);
         Ok(())
     }
 }
 
 impl fmt::Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.inner().write_str(s)
+        with_uart!(self, uart => uart.write_str(s))
     }
 }
 
 /// Global `Console` singleton.
-pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+pub static CONSOLE: IrqMutex<Console> = IrqMutex::new(Console::new());
+
+/// Reads a byte from the console without holding `CONSOLE` while waiting.
+///
+/// In a process, the process blocks in the `wait_input` system call until a
+/// byte arrives, letting other processes run and print. In an exception
+/// handler, where system calls can't be made, this busy-waits instead.
+pub fn read_byte() -> u8 {
+    loop {
+        if let Some(byte) = CONSOLE.lock().try_read_byte() {
+            return byte;
+        }
 
-/// Internal function called by the `kprint[ln]!` macros.
+        if !traps::in_exception() {
+            syscall::wait_input();
+        }
+    }
+}
+
+/// Internal function called by the `kprint[ln]!` macros. Output is mirrored
+/// to the screen if it was initialized.
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     #[cfg(not(test))]
     {
+        use screen::SCREEN;
         use std::fmt::Write;
         let mut console = CONSOLE.lock();
         console.write_fmt(args).unwrap();
+        if let Some(ref mut screen) = *SCREEN.lock() {
+            screen.write_fmt(args).unwrap();
+        }
     }
 
     #[cfg(test)]
@@ -83,6 +210,37 @@ pub fn _print(args: fmt::Arguments) {
     }
 }
 
+/// The verbosity of a kernel log message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+/// The most verbose `LogLevel` that is printed.
+static LOG_LEVEL: AtomicUsize = AtomicUsize::new(LogLevel::Debug as usize);
+
+/// Sets the most verbose `LogLevel` that `klog!` prints to `level`.
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Returns `true` if `klog!` prints messages at `level`.
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as usize <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Like `kprintln!`, but only prints if messages at the `LogLevel` `level`
+/// are enabled.
+pub macro klog($level:expr, $($arg:tt)*) {
+    if log_enabled($level) {
+        kprintln!($($arg)*);
+    }
+}
+
 /// Like `println!`, but for kernel-space.
 pub macro kprintln {
     () => (kprint!("\n")),
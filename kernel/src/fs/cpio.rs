@@ -0,0 +1,228 @@
+use std::str;
+
+/// The magic number at the start of every `newc` header.
+const MAGIC: &[u8] = b"070701";
+
+/// The size of a `newc` header: the magic number followed by thirteen
+/// 8-digit hexadecimal fields.
+pub const HEADER_SIZE: usize = 6 + 13 * 8;
+
+/// The name of the entry marking the end of an archive.
+const TRAILER: &str = "TRAILER!!!";
+
+/// The file type bits of `Entry::mode`.
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+/// Error type for malformed archives.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A header did not start with the `newc` magic number.
+    BadMagic,
+    /// A header field was not a hexadecimal number.
+    BadField,
+    /// An entry name was not NUL-terminated UTF-8.
+    BadName,
+    /// The archive ended before its trailer.
+    Truncated,
+}
+
+/// An entry of a `newc` (SVR4, no CRC) cpio archive.
+#[derive(Debug, Copy, Clone)]
+pub struct Entry<'a> {
+    /// The path of this entry as stored in the archive.
+    pub name: &'a str,
+    pub mode: u32,
+    /// The modification time in seconds since the Unix epoch.
+    pub mtime: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Returns `true` if this entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    /// Returns `true` if this entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+}
+
+/// An iterator over the entries of a `newc` cpio archive.
+///
+/// Iteration stops at the trailer or at the first malformed entry, which is
+/// returned as an `Err`.
+pub struct Entries<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+/// Returns an iterator over the entries of the archive at the start of
+/// `data`. `data` may extend past the end of the archive.
+pub fn entries(data: &[u8]) -> Entries {
+    Entries { data, offset: 0, done: false }
+}
+
+/// Returns `true` if `data` starts with a `newc` cpio header.
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Returns the length of the archive at the start of `data`, including its
+/// trailer, if it is well-formed.
+pub fn archive_len(data: &[u8]) -> Result<usize, Error> {
+    let mut entries = entries(data);
+    while let Some(entry) = entries.next() {
+        entry?;
+    }
+
+    Ok(entries.offset)
+}
+
+/// Rounds `n` up to the next multiple of 4.
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+impl<'a> Entries<'a> {
+    /// Reads the `n`th header field of the header at `self.offset`.
+    fn field(&self, n: usize) -> Result<u32, Error> {
+        let start = self.offset + MAGIC.len() + n * 8;
+        let digits = self.data.get(start..start + 8).ok_or(Error::Truncated)?;
+        let digits = str::from_utf8(digits).map_err(|_| Error::BadField)?;
+        u32::from_str_radix(digits, 16).map_err(|_| Error::BadField)
+    }
+
+    /// Parses the entry at `self.offset`. Returns the entry and the offset of
+    /// the next header.
+    fn parse(&self) -> Result<(Entry<'a>, usize), Error> {
+        let header = self.data
+            .get(self.offset..self.offset + HEADER_SIZE)
+            .ok_or(Error::Truncated)?;
+        if !header.starts_with(MAGIC) {
+            return Err(Error::BadMagic);
+        }
+
+        let mode = self.field(1)?;
+        let mtime = self.field(5)?;
+        let file_size = self.field(6)? as usize;
+        let name_size = self.field(11)? as usize;
+
+        let name_start = self.offset + HEADER_SIZE;
+        let name = self.data
+            .get(name_start..name_start + name_size)
+            .ok_or(Error::Truncated)?;
+        let name = match name.split_last() {
+            Some((&0, name)) => str::from_utf8(name).map_err(|_| Error::BadName)?,
+            _ => return Err(Error::BadName),
+        };
+
+        let data_start = align4(name_start + name_size);
+        let data = self.data
+            .get(data_start..data_start + file_size)
+            .ok_or(Error::Truncated)?;
+
+        let entry = Entry { name, mode, mtime, data };
+        Ok((entry, align4(data_start + file_size)))
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.parse() {
+            Ok((entry, next)) => {
+                self.offset = next;
+                if entry.name == TRAILER {
+                    self.done = true;
+                    return None;
+                }
+                Some(Ok(entry))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Builds an archive of `(name, mode, data)` entries followed by a trailer.
+#[cfg(test)]
+pub fn build(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    fn push_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        let fields = [0, mode, 0, 0, 1, 1234, data.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+        archive.extend_from_slice(MAGIC);
+        for field in fields.iter() {
+            archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        while archive.len() % 4 != 0 {
+            archive.push(0);
+        }
+
+        archive.extend_from_slice(data);
+        while archive.len() % 4 != 0 {
+            archive.push(0);
+        }
+    }
+
+    let mut archive = vec![];
+    for &(name, mode, data) in entries {
+        push_entry(&mut archive, name, mode, data);
+    }
+
+    push_entry(&mut archive, TRAILER, 0, b"");
+    archive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_entries() {
+        let mut archive = build(&[
+            ("bin", S_IFDIR | 0o755, b""),
+            ("bin/hello.txt", S_IFREG | 0o644, b"hello, world!\n"),
+        ]);
+        let len = archive.len();
+        archive.extend_from_slice(b"garbage past the end");
+
+        assert!(is_archive(&archive));
+        assert_eq!(archive_len(&archive), Ok(len));
+
+        let entries: Vec<_> = entries(&archive).map(|e| e.unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "bin");
+        assert!(entries[0].is_dir());
+        assert_eq!(entries[1].name, "bin/hello.txt");
+        assert!(entries[1].is_file());
+        assert_eq!(entries[1].mtime, 1234);
+        assert_eq!(entries[1].data, b"hello, world!\n");
+    }
+
+    #[test]
+    fn check_malformed() {
+        // Drop the trailer: the archive then ends after its only entry.
+        let mut archive = build(&[("file", S_IFREG | 0o644, b"data")]);
+        let len = archive.len() - build(&[]).len();
+        archive.truncate(len);
+        assert_eq!(archive_len(&archive), Err(Error::Truncated));
+
+        archive[0] = b'1';
+        assert_eq!(archive_len(&archive), Err(Error::BadMagic));
+        assert!(!is_archive(&archive));
+    }
+}
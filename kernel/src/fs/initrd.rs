@@ -0,0 +1,543 @@
+use std::io::{self, SeekFrom};
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::{slice, vec};
+
+use fat32::traits;
+
+use allocator::MemoryMap;
+use boot;
+use fs::cpio;
+
+extern "C" {
+    static _end: u8;
+}
+
+/// Returns the initial RAM disk `boot::initialize()` located, if any.
+pub fn locate() -> Option<&'static [u8]> {
+    let (start, size) = boot::info().initrd()?;
+    Some(unsafe { slice::from_raw_parts(start as *const u8, size) })
+}
+
+/// Returns the `(address, size)` of a cpio archive appended to the kernel
+/// image, which is loaded at `_end`, if there is one. Only the header is
+/// checked before the archive is walked, and it may not extend past the
+/// region of `memory` the image ends in.
+///
+/// This must be called before the allocator is initialized: the heap starts
+/// at `_end` and overwrites the archive, as its memory isn't reserved yet.
+pub fn find_appended(memory: &MemoryMap) -> Option<(usize, usize)> {
+    let start = unsafe { &_end as *const u8 as usize };
+    let region = memory.regions()
+        .iter()
+        .find(|region| region.start <= start && start < region.end)?;
+
+    let available = region.end - start;
+    if available < cpio::HEADER_SIZE {
+        return None;
+    }
+
+    let header = unsafe { slice::from_raw_parts(start as *const u8, cpio::HEADER_SIZE) };
+    if !cpio::is_archive(header) {
+        return None;
+    }
+
+    let data = unsafe { slice::from_raw_parts(start as *const u8, available) };
+    let len = cpio::archive_len(data).ok()?;
+    Some((start, len))
+}
+
+/// The mode of directories that only exist implicitly, as a prefix of the
+/// paths of other entries.
+const IMPLICIT_DIR_MODE: u32 = 0o040555;
+
+/// Returns the error for attempts to modify the initrd.
+fn read_only() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "initrd is read-only")
+}
+
+/// Normalizes the path of an archive entry: `./bin/` and `/bin` become `bin`
+/// and `.` becomes the empty string, the root directory.
+fn normalize_name(name: &str) -> &str {
+    let mut name = name;
+    while name.starts_with("./") {
+        name = &name[2..];
+    }
+
+    match name.trim_matches('/') {
+        "." => "",
+        name => name,
+    }
+}
+
+/// Normalizes an absolute lookup path into the form of `normalize_name`.
+fn normalize_path(path: &Path) -> io::Result<String> {
+    let mut components: Vec<&str> = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => {
+                let name = name.to_str().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8")
+                })?;
+                components.push(name);
+            }
+            Component::ParentDir => {
+                components.pop();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(components.join("/"))
+}
+
+/// Returns the parent directory and the name of the normalized `path`.
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("", path),
+    }
+}
+
+/// A read-only file system backed by a `newc` cpio archive in memory.
+///
+/// Directories need not appear in the archive: any path prefix of an entry
+/// is treated as a directory.
+#[derive(Debug, Clone)]
+pub struct Initrd {
+    /// The well-formed entries of the archive with normalized names, parsed
+    /// once when the initrd is mounted.
+    entries: Arc<Vec<cpio::Entry<'static>>>,
+}
+
+impl Initrd {
+    /// Returns a file system over the archive in `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if `data` is not a well-formed
+    /// `newc` cpio archive.
+    pub fn new(data: &'static [u8]) -> io::Result<Initrd> {
+        cpio::archive_len(data).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("malformed initrd: {:?}", e))
+        })?;
+
+        let entries: Vec<_> = cpio::entries(data)
+            .filter_map(|entry| entry.ok())
+            .map(|mut entry| {
+                entry.name = normalize_name(entry.name);
+                entry
+            })
+            .collect();
+
+        Ok(Initrd { entries: Arc::new(entries) })
+    }
+
+    /// Returns the entry at the normalized `path`, if there is one.
+    fn find(&self, path: &str) -> Option<Entry> {
+        if path.is_empty() {
+            return Some(self.implicit_dir("/", ""));
+        }
+
+        let mut implied = false;
+        for entry in self.entries.iter() {
+            if entry.name == path {
+                return Some(self.entry(entry));
+            }
+
+            implied |= entry.name.starts_with(path) && entry.name[path.len()..].starts_with('/');
+        }
+
+        match implied {
+            true => Some(self.implicit_dir(split_path(path).1, path)),
+            false => None,
+        }
+    }
+
+    /// Returns the entries directly inside the directory at the normalized
+    /// `path`.
+    fn children(&self, path: &str) -> Vec<Entry> {
+        let mut children: Vec<Entry> = vec![];
+        for entry in self.entries.iter() {
+            let rest = match path.is_empty() {
+                true => entry.name,
+                false if entry.name.starts_with(path) && entry.name[path.len()..].starts_with('/') => {
+                    &entry.name[path.len() + 1..]
+                }
+                false => continue,
+            };
+
+            if rest.is_empty() {
+                continue;
+            }
+
+            let child = match rest.find('/') {
+                None => self.entry(entry),
+                Some(i) => {
+                    let name = &rest[..i];
+                    let child_path = &entry.name[..entry.name.len() - rest.len() + i];
+                    self.implicit_dir(name, child_path)
+                }
+            };
+
+            match children.iter().position(|c| c.name == child.name) {
+                // An explicit entry replaces an implicit one.
+                Some(i) if child.metadata.mode != IMPLICIT_DIR_MODE => children[i] = child,
+                Some(_) => {}
+                None => children.push(child),
+            }
+        }
+
+        children
+    }
+
+    /// Returns the `Entry` for the archive entry `entry`.
+    fn entry(&self, entry: &cpio::Entry<'static>) -> Entry {
+        let metadata = Metadata {
+            mode: entry.mode,
+            mtime: entry.mtime,
+        };
+
+        let kind = match entry.is_dir() {
+            true => Kind::Dir(Dir {
+                initrd: self.clone(),
+                path: entry.name.to_string(),
+            }),
+            false => Kind::File(File {
+                data: entry.data,
+                offset: 0,
+            }),
+        };
+
+        Entry {
+            name: split_path(entry.name).1.to_string(),
+            metadata,
+            kind,
+        }
+    }
+
+    /// Returns an `Entry` for a directory with no entry of its own.
+    fn implicit_dir(&self, name: &str, path: &str) -> Entry {
+        Entry {
+            name: name.to_string(),
+            metadata: Metadata {
+                mode: IMPLICIT_DIR_MODE,
+                mtime: 0,
+            },
+            kind: Kind::Dir(Dir {
+                initrd: self.clone(),
+                path: path.to_string(),
+            }),
+        }
+    }
+}
+
+impl<'a> traits::FileSystem for &'a Initrd {
+    type File = File;
+    type Dir = Dir;
+    type Entry = Entry;
+
+    fn open<P: AsRef<Path>>(self, path: P) -> io::Result<Self::Entry> {
+        let path = normalize_path(path.as_ref())?;
+        self.find(&path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file or directory"))
+    }
+
+    fn create_file<P: AsRef<Path>>(self, _path: P) -> io::Result<Self::File> {
+        Err(read_only())
+    }
+
+    fn create_dir<P: AsRef<Path>>(self, _path: P, _parents: bool) -> io::Result<Self::Dir> {
+        Err(read_only())
+    }
+
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(self, _from: P, _to: Q) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    fn remove<P: AsRef<Path>>(self, _path: P, _children: bool) -> io::Result<()> {
+        Err(read_only())
+    }
+}
+
+/// A file in an initrd.
+#[derive(Debug, Clone)]
+pub struct File {
+    data: &'static [u8],
+    offset: u64,
+}
+
+impl io::Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset >= self.data.len() as u64 {
+            return Ok(0);
+        }
+
+        let remaining = &self.data[self.offset as usize..];
+        let len = ::std::cmp::min(buf.len(), remaining.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.offset += len as u64;
+        Ok(len)
+    }
+}
+
+impl io::Write for File {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(read_only())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.data.len() as i64 + offset,
+            SeekFrom::Current(offset) => self.offset as i64 + offset,
+        };
+
+        if offset < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative offset",
+            ));
+        }
+
+        self.offset = offset as u64;
+        Ok(self.offset)
+    }
+}
+
+impl traits::File for File {
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// A directory in an initrd.
+#[derive(Debug, Clone)]
+pub struct Dir {
+    initrd: Initrd,
+    /// The normalized path of this directory.
+    path: String,
+}
+
+impl traits::Dir for Dir {
+    type Entry = Entry;
+    type Iter = vec::IntoIter<Entry>;
+
+    fn entries(&self) -> io::Result<Self::Iter> {
+        Ok(self.initrd.children(&self.path).into_iter())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    File(File),
+    Dir(Dir),
+}
+
+/// An entry of a directory in an initrd.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    name: String,
+    metadata: Metadata,
+    kind: Kind,
+}
+
+impl traits::Entry for Entry {
+    type File = File;
+    type Dir = Dir;
+    type Metadata = Metadata;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> &Self::Metadata {
+        &self.metadata
+    }
+
+    fn as_file(&self) -> Option<&Self::File> {
+        match self.kind {
+            Kind::File(ref file) => Some(file),
+            Kind::Dir(_) => None,
+        }
+    }
+
+    fn as_dir(&self) -> Option<&Self::Dir> {
+        match self.kind {
+            Kind::Dir(ref dir) => Some(dir),
+            Kind::File(_) => None,
+        }
+    }
+
+    fn into_file(self) -> Option<Self::File> {
+        match self.kind {
+            Kind::File(file) => Some(file),
+            Kind::Dir(_) => None,
+        }
+    }
+
+    fn into_dir(self) -> Option<Self::Dir> {
+        match self.kind {
+            Kind::Dir(dir) => Some(dir),
+            Kind::File(_) => None,
+        }
+    }
+}
+
+/// Metadata for an entry in an initrd.
+#[derive(Debug, Copy, Clone)]
+pub struct Metadata {
+    mode: u32,
+    /// The modification time in seconds since the Unix epoch.
+    mtime: u32,
+}
+
+impl traits::Metadata for Metadata {
+    type Timestamp = Timestamp;
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn hidden(&self) -> bool {
+        false
+    }
+
+    fn created(&self) -> Self::Timestamp {
+        Timestamp::from_unix(self.mtime)
+    }
+
+    fn accessed(&self) -> Self::Timestamp {
+        Timestamp::from_unix(self.mtime)
+    }
+
+    fn modified(&self) -> Self::Timestamp {
+        Timestamp::from_unix(self.mtime)
+    }
+}
+
+/// A UTC timestamp of an entry in an initrd.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timestamp {
+    year: usize,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl Timestamp {
+    /// Converts `secs` seconds since the Unix epoch into a calendar date and
+    /// time of day.
+    fn from_unix(secs: u32) -> Timestamp {
+        // Days since 0000-03-01, which puts leap days at the end of the year.
+        let days = (secs / 86400) as usize + 719468;
+        let era = days / 146097;
+        let day_of_era = days % 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+        let time = secs % 86400;
+        Timestamp {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+}
+
+impl traits::Timestamp for Timestamp {
+    fn year(&self) -> usize {
+        self.year
+    }
+
+    fn month(&self) -> u8 {
+        self.month
+    }
+
+    fn day(&self) -> u8 {
+        self.day
+    }
+
+    fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    fn second(&self) -> u8 {
+        self.second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fat32::traits::{Dir, Entry, FileSystem};
+    use std::io::Read;
+
+    fn initrd(entries: &[(&str, u32, &[u8])]) -> Initrd {
+        let data = cpio::build(entries).into_boxed_slice();
+        Initrd::new(unsafe { &*Box::into_raw(data) }).unwrap()
+    }
+
+    #[test]
+    fn check_timestamp() {
+        let t = Timestamp::from_unix(0);
+        assert_eq!((t.year, t.month, t.day, t.hour, t.minute, t.second), (1970, 1, 1, 0, 0, 0));
+
+        // 2018-02-28 23:59:59 and the second after it.
+        let t = Timestamp::from_unix(1519862399);
+        assert_eq!((t.year, t.month, t.day, t.hour, t.minute, t.second), (2018, 2, 28, 23, 59, 59));
+        let t = Timestamp::from_unix(1519862400);
+        assert_eq!((t.year, t.month, t.day), (2018, 3, 1));
+    }
+
+    #[test]
+    fn check_lookup() {
+        let initrd = initrd(&[
+            (".", 0o040755, b""),
+            ("./etc/motd", 0o100644, b"welcome!\n"),
+            ("./bin", 0o040755, b""),
+            ("./bin/true", 0o100755, b""),
+        ]);
+
+        let mut motd = (&initrd).open_file("/etc/motd").unwrap();
+        let mut contents = String::new();
+        motd.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "welcome!\n");
+
+        assert!((&initrd).open_dir("/etc").is_ok());
+        assert!((&initrd).open_dir("/bin/../etc").is_ok());
+        assert!((&initrd).open("/etc/passwd").is_err());
+        assert!((&initrd).create_file("/tmp").is_err());
+
+        let root = (&initrd).open_dir("/").unwrap();
+        let names: Vec<String> = root.entries()
+            .unwrap()
+            .map(|entry| entry.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["etc", "bin"]);
+    }
+}
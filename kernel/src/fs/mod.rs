@@ -1,15 +1,32 @@
+mod cpio;
+pub mod initrd;
 pub mod sd;
 
 use std::io;
 use std::path::Path;
+use std::vec;
 
 pub use fat32::traits;
+use fat32::traits::{Dir as DirTrait, Entry as EntryTrait, File as FileTrait};
+use fat32::traits::{FileSystem as FileSystemTrait, Metadata as MetadataTrait};
+use fat32::traits::Timestamp as TimestampTrait;
 use fat32::vfat::{self, Shared, VFat};
 
+use cmdline::Root;
+use console::{klog, LogLevel};
+use self::initrd::Initrd;
 use self::sd::Sd;
 use mutex::Mutex;
 
-pub struct FileSystem(Mutex<Option<Shared<VFat>>>);
+/// A mounted root file system.
+enum Mount {
+    /// The FAT32 file system on the SD card.
+    Fat(Shared<VFat>),
+    /// The read-only initial RAM disk.
+    Initrd(Initrd),
+}
+
+pub struct FileSystem(Mutex<Option<Mount>>);
 
 impl FileSystem {
     /// Returns an uninitialized `FileSystem`.
@@ -20,41 +37,397 @@ impl FileSystem {
         FileSystem(Mutex::new(None))
     }
 
-    /// Initializes the file system.
+    /// Initializes the file system by mounting `root`. If `root` is the SD
+    /// card but it fails to mount, the initrd is mounted instead, if there is
+    /// one. With `Root::None`, nothing is mounted.
     ///
     /// # Panics
     ///
-    /// Panics if the underlying disk or file sytem failed to initialize.
-    pub fn initialize(&self) {
-        let sd = Sd::new().unwrap();
-        let vfat = VFat::from(sd).unwrap();
+    /// Panics if no file system could be mounted.
+    pub fn initialize(&self, root: Root) {
+        let mount = match root {
+            Root::None => return,
+            Root::Sd => mount_sd().or_else(|| {
+                klog!(LogLevel::Info, "fs: falling back to the initrd");
+                mount_initrd()
+            }),
+            Root::Initrd => mount_initrd(),
+        };
 
-        *self.0.lock() = Some(vfat);
+        *self.0.lock() = Some(mount.expect("failed to mount a root file system"));
     }
 }
 
+/// Mounts the first FAT32 partition on the SD card.
+fn mount_sd() -> Option<Mount> {
+    let sd = match Sd::new() {
+        Ok(sd) => sd,
+        Err(e) => {
+            klog!(LogLevel::Warn, "fs: failed to initialize the SD card: {:?}", e);
+            return None;
+        }
+    };
+
+    match VFat::from(sd) {
+        Ok(vfat) => Some(Mount::Fat(vfat)),
+        Err(e) => {
+            klog!(LogLevel::Warn, "fs: failed to mount the SD card: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Mounts the initrd, if there is one.
+fn mount_initrd() -> Option<Mount> {
+    let data = match initrd::locate() {
+        Some(data) => data,
+        None => {
+            klog!(LogLevel::Warn, "fs: no initrd found");
+            return None;
+        }
+    };
+
+    match Initrd::new(data) {
+        Ok(initrd) => Some(Mount::Initrd(initrd)),
+        Err(e) => {
+            klog!(LogLevel::Warn, "fs: failed to mount the initrd: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns the error for operations on a file system that was never
+/// initialized, such as when booting with `root=none`.
+fn unmounted() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no file system mounted")
+}
+
 impl<'a> traits::FileSystem for &'a FileSystem {
-    type File = vfat::File;
-    type Dir = vfat::Dir;
-    type Entry = vfat::Entry;
+    type File = File;
+    type Dir = Dir;
+    type Entry = Entry;
 
     fn open<P: AsRef<Path>>(self, path: P) -> io::Result<Self::Entry> {
-        self.0.lock().as_ref().unwrap().open(path)
+        match *self.0.lock() {
+            Some(Mount::Fat(ref vfat)) => vfat.open(path).and_then(Entry::from_fat),
+            Some(Mount::Initrd(ref initrd)) => initrd.open(path).and_then(Entry::from_initrd),
+            None => Err(unmounted()),
+        }
     }
 
     fn create_file<P: AsRef<Path>>(self, path: P) -> io::Result<Self::File> {
-        self.0.lock().as_ref().unwrap().create_file(path)
+        match *self.0.lock() {
+            Some(Mount::Fat(ref vfat)) => vfat.create_file(path).map(File::Fat),
+            Some(Mount::Initrd(ref initrd)) => initrd.create_file(path).map(File::Initrd),
+            None => Err(unmounted()),
+        }
     }
 
     fn create_dir<P: AsRef<Path>>(self, path: P, parents: bool) -> io::Result<Self::Dir> {
-        self.0.lock().as_ref().unwrap().create_dir(path, parents)
+        match *self.0.lock() {
+            Some(Mount::Fat(ref vfat)) => vfat.create_dir(path, parents).map(Dir::Fat),
+            Some(Mount::Initrd(ref initrd)) => initrd.create_dir(path, parents).map(Dir::Initrd),
+            None => Err(unmounted()),
+        }
     }
 
     fn rename<P: AsRef<Path>, Q: AsRef<Path>>(self, from: P, to: Q) -> io::Result<()> {
-        self.0.lock().as_ref().unwrap().rename(from, to)
+        match *self.0.lock() {
+            Some(Mount::Fat(ref vfat)) => vfat.rename(from, to),
+            Some(Mount::Initrd(ref initrd)) => initrd.rename(from, to),
+            None => Err(unmounted()),
+        }
     }
 
     fn remove<P: AsRef<Path>>(self, path: P, children: bool) -> io::Result<()> {
-        self.0.lock().as_ref().unwrap().remove(path, children)
+        match *self.0.lock() {
+            Some(Mount::Fat(ref vfat)) => vfat.remove(path, children),
+            Some(Mount::Initrd(ref initrd)) => initrd.remove(path, children),
+            None => Err(unmounted()),
+        }
+    }
+}
+
+/// A file on the mounted file system.
+pub enum File {
+    Fat(vfat::File),
+    Initrd(initrd::File),
+}
+
+impl io::Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            File::Fat(ref mut file) => file.read(buf),
+            File::Initrd(ref mut file) => file.read(buf),
+        }
+    }
+}
+
+impl io::Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            File::Fat(ref mut file) => file.write(buf),
+            File::Initrd(ref mut file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            File::Fat(ref mut file) => file.flush(),
+            File::Initrd(ref mut file) => file.flush(),
+        }
+    }
+}
+
+impl io::Seek for File {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match *self {
+            File::Fat(ref mut file) => file.seek(pos),
+            File::Initrd(ref mut file) => file.seek(pos),
+        }
+    }
+}
+
+impl traits::File for File {
+    fn sync(&mut self) -> io::Result<()> {
+        match *self {
+            File::Fat(ref mut file) => file.sync(),
+            File::Initrd(ref mut file) => file.sync(),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        match *self {
+            File::Fat(ref file) => file.size(),
+            File::Initrd(ref file) => file.size(),
+        }
+    }
+}
+
+/// A directory on the mounted file system.
+pub enum Dir {
+    Fat(vfat::Dir),
+    Initrd(initrd::Dir),
+}
+
+impl traits::Dir for Dir {
+    type Entry = Entry;
+    type Iter = vec::IntoIter<Entry>;
+
+    /// Entries that are neither files nor directories, such as FAT volume
+    /// labels, are skipped.
+    fn entries(&self) -> io::Result<Self::Iter> {
+        let entries: Vec<Entry> = match *self {
+            Dir::Fat(ref dir) => dir.entries()?.filter_map(|e| Entry::from_fat(e).ok()).collect(),
+            Dir::Initrd(ref dir) => {
+                dir.entries()?.filter_map(|e| Entry::from_initrd(e).ok()).collect()
+            }
+        };
+
+        Ok(entries.into_iter())
+    }
+}
+
+enum Kind {
+    File(File),
+    Dir(Dir),
+}
+
+/// An entry of a directory on the mounted file system.
+pub struct Entry {
+    name: String,
+    metadata: Metadata,
+    kind: Kind,
+}
+
+impl Entry {
+    /// Wraps `entry` of one of the underlying file systems, converting its
+    /// file or directory with `file` or `dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if `entry` is neither a file
+    /// nor a directory, such as a FAT volume label.
+    fn wrap<E: EntryTrait>(
+        entry: E,
+        metadata: Metadata,
+        file: fn(E::File) -> File,
+        dir: fn(E::Dir) -> Dir,
+    ) -> io::Result<Entry> {
+        let name = entry.name().to_string();
+        let kind = match entry.is_file() {
+            true => entry.into_file().map(|f| Kind::File(file(f))),
+            false => entry.into_dir().map(|d| Kind::Dir(dir(d))),
+        };
+
+        match kind {
+            Some(kind) => Ok(Entry { name, metadata, kind }),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "entry is neither a file nor a directory",
+            )),
+        }
+    }
+
+    /// Wraps an entry of the FAT32 file system. See `wrap()`.
+    fn from_fat(entry: vfat::Entry) -> io::Result<Entry> {
+        let metadata = Metadata::Fat(entry.metadata().clone());
+        Entry::wrap(entry, metadata, File::Fat, Dir::Fat)
+    }
+
+    /// Wraps an entry of the initrd. See `wrap()`.
+    fn from_initrd(entry: initrd::Entry) -> io::Result<Entry> {
+        let metadata = Metadata::Initrd(*entry.metadata());
+        Entry::wrap(entry, metadata, File::Initrd, Dir::Initrd)
+    }
+}
+
+impl traits::Entry for Entry {
+    type File = File;
+    type Dir = Dir;
+    type Metadata = Metadata;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> &Self::Metadata {
+        &self.metadata
+    }
+
+    fn as_file(&self) -> Option<&Self::File> {
+        match self.kind {
+            Kind::File(ref file) => Some(file),
+            Kind::Dir(_) => None,
+        }
+    }
+
+    fn as_dir(&self) -> Option<&Self::Dir> {
+        match self.kind {
+            Kind::Dir(ref dir) => Some(dir),
+            Kind::File(_) => None,
+        }
+    }
+
+    fn into_file(self) -> Option<Self::File> {
+        match self.kind {
+            Kind::File(file) => Some(file),
+            Kind::Dir(_) => None,
+        }
+    }
+
+    fn into_dir(self) -> Option<Self::Dir> {
+        match self.kind {
+            Kind::Dir(dir) => Some(dir),
+            Kind::File(_) => None,
+        }
+    }
+}
+
+/// Metadata for an entry on the mounted file system.
+#[derive(Clone)]
+pub enum Metadata {
+    Fat(vfat::Metadata),
+    Initrd(initrd::Metadata),
+}
+
+impl Metadata {
+    /// Returns `true` if the entry is a FAT volume label rather than a file
+    /// or directory.
+    pub fn volume_id(&self) -> bool {
+        match *self {
+            Metadata::Fat(ref metadata) => metadata.volume_id(),
+            Metadata::Initrd(_) => false,
+        }
+    }
+}
+
+impl traits::Metadata for Metadata {
+    type Timestamp = Timestamp;
+
+    fn read_only(&self) -> bool {
+        match *self {
+            Metadata::Fat(ref metadata) => metadata.read_only(),
+            Metadata::Initrd(ref metadata) => metadata.read_only(),
+        }
+    }
+
+    fn hidden(&self) -> bool {
+        match *self {
+            Metadata::Fat(ref metadata) => metadata.hidden(),
+            Metadata::Initrd(ref metadata) => metadata.hidden(),
+        }
+    }
+
+    fn created(&self) -> Self::Timestamp {
+        match *self {
+            Metadata::Fat(ref metadata) => Timestamp::Fat(metadata.created()),
+            Metadata::Initrd(ref metadata) => Timestamp::Initrd(metadata.created()),
+        }
+    }
+
+    fn accessed(&self) -> Self::Timestamp {
+        match *self {
+            Metadata::Fat(ref metadata) => Timestamp::Fat(metadata.accessed()),
+            Metadata::Initrd(ref metadata) => Timestamp::Initrd(metadata.accessed()),
+        }
+    }
+
+    fn modified(&self) -> Self::Timestamp {
+        match *self {
+            Metadata::Fat(ref metadata) => Timestamp::Fat(metadata.modified()),
+            Metadata::Initrd(ref metadata) => Timestamp::Initrd(metadata.modified()),
+        }
+    }
+}
+
+/// A timestamp of an entry on the mounted file system.
+#[derive(Copy, Clone)]
+pub enum Timestamp {
+    Fat(vfat::Timestamp),
+    Initrd(initrd::Timestamp),
+}
+
+impl traits::Timestamp for Timestamp {
+    fn year(&self) -> usize {
+        match *self {
+            Timestamp::Fat(ref t) => t.year(),
+            Timestamp::Initrd(ref t) => t.year(),
+        }
+    }
+
+    fn month(&self) -> u8 {
+        match *self {
+            Timestamp::Fat(ref t) => t.month(),
+            Timestamp::Initrd(ref t) => t.month(),
+        }
+    }
+
+    fn day(&self) -> u8 {
+        match *self {
+            Timestamp::Fat(ref t) => t.day(),
+            Timestamp::Initrd(ref t) => t.day(),
+        }
+    }
+
+    fn hour(&self) -> u8 {
+        match *self {
+            Timestamp::Fat(ref t) => t.hour(),
+            Timestamp::Initrd(ref t) => t.hour(),
+        }
+    }
+
+    fn minute(&self) -> u8 {
+        match *self {
+            Timestamp::Fat(ref t) => t.minute(),
+            Timestamp::Initrd(ref t) => t.minute(),
+        }
+    }
+
+    fn second(&self) -> u8 {
+        match *self {
+            Timestamp::Fat(ref t) => t.second(),
+            Timestamp::Initrd(ref t) => t.second(),
+        }
     }
 }
@@ -0,0 +1,48 @@
+use pi::gpio::{self, Gpio, Input};
+
+use mutex::IrqMutex;
+use traps::irq;
+
+/// A function called in the IRQ handler with the number of a pin on which
+/// an event was detected.
+pub type Handler = fn(u8);
+
+/// The number of GPIO pins.
+const PINS: usize = 54;
+
+/// The handler registered for each pin, if any.
+static HANDLERS: IrqMutex<[Option<Handler>; PINS]> = IrqMutex::new([None; PINS]);
+
+/// Calls `handler` from the IRQ handler whenever an event is detected on
+/// `pin`, replacing any handler registered before, and enables the pin's
+/// interrupt. Events are enabled with `Gpio::enable_event()`.
+///
+/// Events are cleared before `handler` is called, so a handler for a level
+/// event must disable it or change the level to not be called again.
+pub fn register(pin: &Gpio<Input>, handler: Handler) {
+    HANDLERS.lock()[pin.pin() as usize] = Some(handler);
+    irq::register(pin.interrupt(), |_, _| handle_irq());
+}
+
+/// Removes the handler registered for `pin`. Events detected on the pin are
+/// then cleared and ignored.
+pub fn unregister(pin: &Gpio<Input>) {
+    HANDLERS.lock()[pin.pin() as usize] = None;
+}
+
+/// Clears all detected GPIO events and calls the handlers of the pins they
+/// were detected on. Called when a GPIO interrupt is pending.
+pub fn handle_irq() {
+    let events = gpio::pending_events();
+    gpio::clear_events(events);
+
+    // Handlers may register and unregister handlers.
+    let handlers = *HANDLERS.lock();
+    for (pin, handler) in handlers.iter().enumerate() {
+        if events & (1 << pin) != 0 {
+            if let Some(handler) = *handler {
+                handler(pin as u8);
+            }
+        }
+    }
+}
@@ -24,19 +24,29 @@ extern crate stack_vec;
 
 pub mod aarch64;
 pub mod allocator;
+pub mod boot;
+pub mod cmdline;
 pub mod console;
 pub mod fs;
+pub mod gpio;
 pub mod lang_items;
 pub mod mutex;
 pub mod process;
+pub mod screen;
 pub mod shell;
+pub mod smp;
+pub mod sync;
+pub mod syscall;
+pub mod timer;
 pub mod traps;
 pub mod vm;
 
 #[cfg(not(test))]
 use allocator::Allocator;
+use cmdline::Params;
 use console::kprint;
 use console::kprintln;
+use console::CONSOLE;
 use fat32::traits::BlockDevice;
 use fs::sd::Sd;
 use fs::FileSystem;
@@ -97,10 +107,40 @@ pub fn check_root_dir() {
 
 #[no_mangle]
 #[cfg(not(test))]
-pub extern "C" fn kmain() {
+pub extern "C" fn kmain(boot_info: usize) {
     pi::timer::spin_sleep_ms(1000);
 
+    // Exclusive accesses, which locks need, only work with the MMU on.
+    vm::initialize(boot::BootInfo::from_addr(boot_info));
+    boot::initialize(boot_info);
     ALLOCATOR.initialize();
-    FILE_SYSTEM.initialize();
-    SCHEDULER.start();
+
+    let params = Params::parse(boot::info().cmdline().unwrap_or(""));
+    console::set_log_level(params.log_level);
+    CONSOLE.lock().select(params.console);
+    if let Some(baud) = params.baud {
+        let result = CONSOLE.lock().set_baud_rate(baud);
+        if result.is_err() {
+            kprintln!("console: unachievable baud rate {}", baud);
+        }
+    }
+    if params.screen {
+        if let Err(e) = screen::initialize() {
+            kprintln!("screen: failed to initialize: {:?}", e);
+        }
+    }
+    process::set_tick(params.tick);
+    timer::initialize();
+    CONSOLE.lock().enable_interrupts();
+
+    FILE_SYSTEM.initialize(params.root);
+    SCHEDULER.start(params.shell);
+}
+
+/// The Rust entry point of cores 1 through 3, once `smp` released them.
+#[no_mangle]
+#[cfg(not(test))]
+pub extern "C" fn kmain_secondary() {
+    vm::enable();
+    SCHEDULER.start_secondary();
 }
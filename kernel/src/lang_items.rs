@@ -1,4 +1,5 @@
-use console::kprintln;
+use console::{kprintln, CONSOLE};
+use screen::SCREEN;
 
 #[no_mangle]
 #[cfg(not(test))]
@@ -9,6 +10,13 @@ pub extern "C" fn panic_fmt(
     line: u32,
     col: u32,
 ) -> ! {
+    // The panic may have interrupted printing on this core, whose guards are
+    // never dropped now. Locks other cores hold are waited for as usual.
+    unsafe {
+        CONSOLE.force_unlock();
+        SCREEN.force_unlock();
+    }
+
     kprintln!(" __________");
     kprintln!("( OH SHI-- )");
     kprintln!(" ----------");
@@ -22,6 +30,7 @@ pub extern "C" fn panic_fmt(
     kprintln!();
     kprintln!("paniced: {}", fmt);
     kprintln!("    --> {}:{}:{}", file, line, col);
+    CONSOLE.lock().flush();
 
     loop {
         unsafe { asm!("wfe") }
@@ -1,13 +1,21 @@
 use std::cell::UnsafeCell;
 use std::fmt;
 use std::ops::{Deref, DerefMut, Drop};
-use std::sync::atomic::Ordering::Relaxed;
-use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use std::sync::atomic::{spin_loop_hint, AtomicBool, AtomicUsize};
 
+use aarch64;
+
+/// A spinlock. Exclusive accesses only work with the MMU and data caches on,
+/// so nothing may be locked before `vm::initialize()`.
+///
+/// A lock that an interrupt handler takes must be an `IrqMutex` instead, or
+/// the handler spins forever if it interrupts the lock's holder.
 #[repr(align(32))]
 pub struct Mutex<T> {
     data: UnsafeCell<T>,
     lock: AtomicBool,
+    /// The core that took the lock, or `usize::max_value()` if it's free.
     owner: AtomicUsize,
 }
 
@@ -32,34 +40,65 @@ impl<T> Mutex<T> {
 }
 
 impl<T> Mutex<T> {
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
+    /// Takes the lock for the current core if it's free. Returns `true` if it
+    /// was taken.
+    fn acquire(&self) -> bool {
+        if self.lock.compare_exchange(false, true, Acquire, Relaxed).is_err() {
+            return false;
+        }
+
+        self.owner.store(aarch64::affinity(), Relaxed);
+        true
+    }
+
+    /// Spins until the lock looks free, without writing to it, so waiting
+    /// cores don't keep taking the cache line from each other.
+    fn wait(&self) {
+        while self.lock.load(Relaxed) {
+            spin_loop_hint();
+        }
+    }
+
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let this = 0;
-        if !self.lock.load(Relaxed) || self.owner.load(Relaxed) == this {
-            self.lock.store(true, Relaxed);
-            self.owner.store(this, Relaxed);
-            Some(MutexGuard { lock: &self })
-        } else {
-            None
+        match self.acquire() {
+            true => Some(MutexGuard { lock: &self }),
+            false => None,
         }
     }
 
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
     #[inline(never)]
     pub fn lock(&self) -> MutexGuard<T> {
-        // Wait until we can "aquire" the lock, then "acquire" it.
         loop {
             match self.try_lock() {
                 Some(guard) => return guard,
-                None => continue,
+                None => self.wait(),
             }
         }
     }
 
+    /// Returns the core that took the lock, if it's held.
+    pub fn owner(&self) -> Option<usize> {
+        match self.owner.load(Relaxed) {
+            owner if owner == usize::max_value() => None,
+            owner => Some(owner),
+        }
+    }
+
+    /// Frees the lock if the current core took it. For the panic handler,
+    /// which must print even if it interrupted the console's holder.
+    ///
+    /// # Safety
+    ///
+    /// The guard that holds the lock must never be used again.
+    pub unsafe fn force_unlock(&self) {
+        if self.owner() == Some(aarch64::affinity()) {
+            self.unlock();
+        }
+    }
+
     fn unlock(&self) {
-        self.lock.store(false, Relaxed);
+        self.owner.store(usize::max_value(), Relaxed);
+        self.lock.store(false, Release);
     }
 }
 
@@ -91,3 +130,93 @@ impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
         }
     }
 }
+
+/// A spinlock that masks IRQs and FIQs on the holding core while it's held,
+/// for data that interrupt handlers share with processes, such as the
+/// console and the scheduler. Interrupts are unmasked again while waiting.
+///
+/// Each guard restores the interrupt masks from before its lock was taken.
+/// Guards must be dropped in the reverse order their locks were taken:
+/// dropping an outer guard first unmasks interrupts while the inner lock is
+/// still held.
+pub struct IrqMutex<T>(Mutex<T>);
+
+pub struct IrqMutexGuard<'a, T: 'a> {
+    lock: &'a Mutex<T>,
+    /// The value of `DAIF` before the lock was taken.
+    daif: u64,
+}
+
+impl<'a, T> !Send for IrqMutexGuard<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for IrqMutexGuard<'a, T> {}
+
+impl<T> IrqMutex<T> {
+    pub const fn new(val: T) -> IrqMutex<T> {
+        IrqMutex(Mutex::new(val))
+    }
+
+    pub fn try_lock(&self) -> Option<IrqMutexGuard<T>> {
+        let daif = aarch64::mask_interrupts();
+        match self.0.acquire() {
+            true => Some(IrqMutexGuard { lock: &self.0, daif }),
+            false => {
+                aarch64::restore_interrupts(daif);
+                None
+            }
+        }
+    }
+
+    #[inline(never)]
+    pub fn lock(&self) -> IrqMutexGuard<T> {
+        loop {
+            match self.try_lock() {
+                Some(guard) => return guard,
+                None => self.0.wait(),
+            }
+        }
+    }
+
+    /// Returns the core that took the lock, if it's held.
+    pub fn owner(&self) -> Option<usize> {
+        self.0.owner()
+    }
+
+    /// Frees the lock if the current core took it. Interrupts stay masked.
+    ///
+    /// # Safety
+    ///
+    /// The guard that holds the lock must never be used again.
+    pub unsafe fn force_unlock(&self) {
+        self.0.force_unlock()
+    }
+}
+
+impl<'a, T: 'a> Deref for IrqMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> DerefMut for IrqMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> Drop for IrqMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+        aarch64::restore_interrupts(self.daif);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for IrqMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f.debug_struct("IrqMutex").field("data", &&*guard).finish(),
+            None => f.debug_struct("IrqMutex").field("data", &"<locked>").finish(),
+        }
+    }
+}
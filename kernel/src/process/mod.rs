@@ -4,6 +4,6 @@ mod stack;
 mod state;
 
 pub use self::process::{Id, Process};
-pub use self::scheduler::{GlobalScheduler, TICK};
+pub use self::scheduler::{set_tick, tick, GlobalScheduler, TICK};
 pub use self::stack::Stack;
 pub use self::state::State;
@@ -1,3 +1,5 @@
+use std::mem;
+
 use process::{Stack, State};
 use traps::TrapFrame;
 
@@ -46,6 +48,17 @@ impl Process {
     ///
     /// Returns `false` in all other cases.
     pub fn is_ready(&mut self) -> bool {
-        unimplemented!("Process::is_ready()")
+        let mut state = mem::replace(&mut self.state, State::Ready);
+        let ready = match state {
+            State::Ready => true,
+            State::Waiting(ref mut event_poll) => event_poll(self),
+            State::Running => false,
+        };
+
+        if !ready {
+            self.state = state;
+        }
+
+        ready
     }
 }
@@ -1,25 +1,41 @@
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use mutex::Mutex;
+use aarch64::{self, affinity};
+use aarch64::timer::tick_in;
+use mutex::IrqMutex;
 use process::{Id, Process, State};
 use shell;
+use smp::{self, NUM_CORES};
 use traps::TrapFrame;
 
-use pi::interrupt::{Controller, Interrupt};
-use pi::timer::tick_in;
+use pi::interrupt::{CoreTimer, LocalController};
 
-/// The `tick` time.
+/// The default `tick` time.
 // FIXME: When you're ready, change this to something more reasonable.
 pub const TICK: u32 = 2 * 1000 * 1000;
 
+/// The current `tick` time in microseconds.
+static CURRENT_TICK: AtomicUsize = AtomicUsize::new(TICK as usize);
+
+/// Returns the current `tick` time in microseconds.
+pub fn tick() -> u32 {
+    CURRENT_TICK.load(Ordering::Relaxed) as u32
+}
+
+/// Sets the `tick` time to `us` microseconds. Takes effect at the next tick.
+pub fn set_tick(us: u32) {
+    CURRENT_TICK.store(us as usize, Ordering::Relaxed);
+}
+
 /// Process scheduler for the entire machine.
 #[derive(Debug)]
-pub struct GlobalScheduler(Mutex<Option<Scheduler>>);
+pub struct GlobalScheduler(IrqMutex<Option<Scheduler>>);
 
 impl GlobalScheduler {
     /// Returns an uninitialized wrapper around a local scheduler.
     pub const fn uninitialized() -> GlobalScheduler {
-        GlobalScheduler(Mutex::new(None))
+        GlobalScheduler(IrqMutex::new(None))
     }
 
     /// Adds a process to the scheduler's queue and returns that process's ID.
@@ -35,40 +51,88 @@ impl GlobalScheduler {
     /// Performs a context switch using `tf` by setting the state of the current
     /// process to `new_state`, saving `tf` into the current process, and
     /// restoring the next process's trap frame into `tf`. For more details, see
-    /// the documentation on `Scheduler::switch()`.
+    /// the documentation on `Scheduler::save()` and `Scheduler::schedule()`.
+    ///
+    /// This method blocks until there is a process to switch to, conserving
+    /// energy as much as possible in the interim. The scheduler isn't locked
+    /// while waiting, so the other cores can keep scheduling.
     #[must_use]
     pub fn switch(&self, new_state: State, tf: &mut TrapFrame) -> Option<Id> {
         self.0
             .lock()
             .as_mut()
             .expect("scheduler uninitialized")
-            .switch(new_state, tf)
+            .save(new_state, tf)?;
+
+        loop {
+            let id = self.0
+                .lock()
+                .as_mut()
+                .expect("scheduler uninitialized")
+                .schedule(tf);
+            if id.is_some() {
+                return id;
+            }
+
+            aarch64::wfi();
+        }
     }
 
-    /// Initializes the scheduler and starts executing processes in user space
-    /// using timer interrupt based preemptive scheduling. This method should
-    /// not return under normal conditions.
-    pub fn start(&self) {
+    /// Initializes the scheduler, starts the secondary cores, and starts
+    /// executing processes in user space using timer interrupt based
+    /// preemptive scheduling. The first process runs the shell if `shell` is
+    /// `true` and idles otherwise. This method should not return under normal
+    /// conditions.
+    pub fn start(&self, shell: bool) {
         let mut process = Process::new().unwrap();
-        process.trap_frame.elr = run_shell as u64;
+        process.trap_frame.elr = match shell {
+            true => run_shell as u64,
+            false => idle as u64,
+        };
+
+        *self.0.lock() = Some(Scheduler::new());
+        smp::start_secondary_cores();
+        self.run(process);
+    }
+
+    /// Starts executing processes on a secondary core, beginning with an idle
+    /// process of its own. `start()` must have initialized the scheduler.
+    pub fn start_secondary(&self) {
+        let mut process = Process::new().unwrap();
+        process.trap_frame.elr = idle as u64;
+        self.run(process);
+    }
+
+    /// Makes `process` the current process of this core and starts executing
+    /// it in user space, with this core's timer interrupt enabled.
+    fn run(&self, mut process: Process) {
         process.trap_frame.sp = process.stack.top().as_u64();
         process.trap_frame.spsr = 0;
-        let tf = &*process.trap_frame;
 
-        let mut controller = Controller::new();
-        controller.enable(Interrupt::Timer1);
+        // The trap frame is boxed, so it stays put when `process` is moved
+        // into the scheduler's queue.
+        let tf = &*process.trap_frame as *const TrapFrame;
+        self.0
+            .lock()
+            .as_mut()
+            .expect("scheduler uninitialized")
+            .start(process)
+            .expect("failed to schedule the first process");
+
+        // Each core preempts its processes with its own generic timer.
+        let core = affinity();
+        LocalController::new(core).enable_timer(CoreTimer::Physical);
 
-        tick_in(TICK);
+        tick_in(tick());
 
         unsafe {
             asm!("mov sp, $0
                   bl context_restore
-                  adr x0, _start
-                  mov sp, x0
+                  mov sp, $1
                   mov x0, xzr
                   mov lr, xzr
                   eret"
-                :: "r"(tf)
+                :: "r"(tf), "r"(smp::stack_top(core))
                 :: "volatile");
         }
     }
@@ -77,38 +141,75 @@ impl GlobalScheduler {
 #[derive(Debug)]
 struct Scheduler {
     processes: VecDeque<Process>,
-    current: Option<Id>,
+    /// The ID of the process running on each core, if any.
+    current: [Option<Id>; NUM_CORES],
     last_id: Option<Id>,
 }
 
 impl Scheduler {
     /// Returns a new `Scheduler` with an empty queue.
     fn new() -> Scheduler {
-        unimplemented!("Scheduler::new()")
+        Scheduler {
+            processes: VecDeque::new(),
+            current: [None; NUM_CORES],
+            last_id: None,
+        }
     }
 
     /// Adds a process to the scheduler's queue and returns that process's ID if
     /// a new process can be scheduled. The process ID is newly allocated for
     /// the process and saved in its `trap_frame`. If no further processes can
     /// be scheduled, returns `None`.
-    ///
-    /// If this is the first process added, it is marked as the current process.
-    /// It is the caller's responsibility to ensure that the first time `switch`
-    /// is called, that process is executing on the CPU.
     fn add(&mut self, mut process: Process) -> Option<Id> {
-        unimplemented!("Scheduler::add()")
+        let id = match self.last_id {
+            Some(last_id) => last_id.checked_add(1)?,
+            None => 0,
+        };
+
+        process.trap_frame.tpidr = id;
+        self.last_id = Some(id);
+        self.processes.push_back(process);
+        Some(id)
     }
 
-    /// Sets the current process's state to `new_state`, finds the next process
-    /// to switch to, and performs the context switch on `tf` by saving `tf`
-    /// into the current process and restoring the next process's trap frame
-    /// into `tf`. If there is no current process, returns `None`. Otherwise,
-    /// returns `Some` of the process ID that was context switched into `tf`.
-    ///
-    /// This method blocks until there is a process to switch to, conserving
-    /// energy as much as possible in the interim.
-    fn switch(&mut self, new_state: State, tf: &mut TrapFrame) -> Option<Id> {
-        unimplemented!("Scheduler::switch()")
+    /// Adds a process to the scheduler's queue as this core's current
+    /// process, in the `Running` state, and returns its ID. It is the
+    /// caller's responsibility to ensure that the first time `save` is
+    /// called on this core, that process is executing on it.
+    fn start(&mut self, mut process: Process) -> Option<Id> {
+        process.state = State::Running;
+        let id = self.add(process)?;
+        self.current[affinity()] = Some(id);
+        Some(id)
+    }
+
+    /// Sets the state of this core's current process to `new_state`, saves
+    /// `tf` into it and moves it to the back of the queue. The core then has
+    /// no current process. If there was none, returns `None`.
+    fn save(&mut self, new_state: State, tf: &TrapFrame) -> Option<()> {
+        let id = self.current[affinity()].take()?;
+        let index = self.processes.iter().position(|p| p.trap_frame.tpidr == id)?;
+        let mut current = self.processes.remove(index)?;
+        *current.trap_frame = *tf;
+        current.state = new_state;
+        self.processes.push_back(current);
+        Some(())
+    }
+
+    /// Finds the first process in the queue that is ready, makes it this
+    /// core's current process and restores its trap frame into `tf`. Returns
+    /// its ID, or `None` if no process is ready. Processes running on other
+    /// cores are never ready.
+    fn schedule(&mut self, tf: &mut TrapFrame) -> Option<Id> {
+        let index = self.processes.iter_mut().position(|p| p.is_ready())?;
+        let mut next = self.processes.remove(index)?;
+        next.state = State::Running;
+        *tf = *next.trap_frame;
+
+        let id = next.trap_frame.tpidr;
+        self.current[affinity()] = Some(id);
+        self.processes.push_back(next);
+        Some(id)
     }
 }
 
@@ -127,3 +228,11 @@ extern "C" fn run_shell() {
         shell::shell("user1> ");
     }
 }
+
+/// Waits for interrupts forever. Runs at EL0, where `SCTLR_EL1.nTWE` lets it
+/// execute `wfe` without trapping.
+extern "C" fn idle() {
+    loop {
+        aarch64::wfe();
+    }
+}
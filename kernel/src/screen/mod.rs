@@ -0,0 +1,465 @@
+mod font;
+
+use std::fmt;
+
+use pi::framebuffer::{self, Framebuffer};
+
+use mutex::IrqMutex;
+
+/// The escape character, which starts an ANSI escape sequence.
+const ESC: u8 = 0x1B;
+
+/// The most parameters kept from a control sequence. Later ones are ignored.
+const MAX_PARAMS: usize = 4;
+
+/// Tab stops are every `TAB_WIDTH` columns.
+const TAB_WIDTH: u32 = 8;
+
+/// Glyphs are scaled up to keep the screen about this many columns wide.
+const TARGET_COLUMNS: u32 = 80;
+
+/// The 16 ANSI colours as `(red, green, blue)`: black, red, green, yellow,
+/// blue, magenta, cyan and white, then their bright variants.
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (170, 0, 0),
+    (0, 170, 0),
+    (170, 85, 0),
+    (0, 0, 170),
+    (170, 0, 170),
+    (0, 170, 170),
+    (170, 170, 170),
+    (85, 85, 85),
+    (255, 85, 85),
+    (85, 255, 85),
+    (255, 255, 85),
+    (85, 85, 255),
+    (255, 85, 255),
+    (85, 255, 255),
+    (255, 255, 255),
+];
+
+/// Error type for setting up the screen.
+#[derive(Debug)]
+pub enum Error {
+    /// The firmware couldn't allocate a framebuffer.
+    Framebuffer(framebuffer::Error),
+    /// The display is too small to hold a single character.
+    TooSmall,
+}
+
+impl From<framebuffer::Error> for Error {
+    fn from(error: framebuffer::Error) -> Error {
+        Error::Framebuffer(error)
+    }
+}
+
+/// The default foreground and background colours, indices into `PALETTE`.
+const DEFAULT_FOREGROUND: u8 = 7;
+const DEFAULT_BACKGROUND: u8 = 0;
+
+/// Something to do in response to output bytes.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// A byte outside of an escape sequence.
+    Byte(u8),
+    /// A control sequence, `ESC [ params command`. Missing parameters are 0.
+    Control {
+        command: u8,
+        params: [u32; MAX_PARAMS],
+        len: usize,
+    },
+}
+
+impl Action {
+    /// Returns the parameter at `index` of a control sequence, or `default`
+    /// if it was missing or 0.
+    fn param(&self, index: usize, default: u32) -> u32 {
+        match *self {
+            Action::Control { params, len, .. } if index < len && params[index] != 0 => {
+                params[index]
+            }
+            _ => default,
+        }
+    }
+}
+
+/// The states of the escape sequence parser.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Control,
+}
+
+/// Splits output bytes into plain bytes and ANSI escape sequences. Escape
+/// sequences other than control sequences (`ESC [`) are dropped.
+struct Parser {
+    state: State,
+    params: [u32; MAX_PARAMS],
+    /// The index of the parameter being read.
+    index: usize,
+}
+
+impl Parser {
+    /// Returns a new parser outside of any escape sequence.
+    fn new() -> Parser {
+        Parser {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            index: 0,
+        }
+    }
+
+    /// Feeds `byte` to the parser. Returns the action it completes, if any.
+    fn feed(&mut self, byte: u8) -> Option<Action> {
+        match self.state {
+            State::Ground if byte == ESC => self.state = State::Escape,
+            State::Ground => return Some(Action::Byte(byte)),
+            State::Escape if byte == b'[' => {
+                self.state = State::Control;
+                self.params = [0; MAX_PARAMS];
+                self.index = 0;
+            }
+            // Intermediate bytes, as in `ESC ( B`, precede the final byte.
+            State::Escape => match byte {
+                0x20...0x2F => {}
+                _ => self.state = State::Ground,
+            },
+            State::Control => match byte {
+                b'0'...b'9' => {
+                    if let Some(param) = self.params.get_mut(self.index) {
+                        *param = param.saturating_mul(10).saturating_add((byte - b'0') as u32);
+                    }
+                }
+                b';' => self.index = self.index.saturating_add(1),
+                0x40...0x7E => {
+                    self.state = State::Ground;
+                    return Some(Action::Control {
+                        command: byte,
+                        params: self.params,
+                        len: MAX_PARAMS.min(self.index + 1),
+                    });
+                }
+                // Private markers and intermediate bytes aren't supported,
+                // but must not end the sequence.
+                _ => {}
+            },
+        }
+
+        None
+    }
+}
+
+/// A text console drawn on a framebuffer.
+///
+/// Output is interpreted as a VT100-style terminal would: `\n` starts a new
+/// line, `\r`, backspace and tab move the cursor, and the screen scrolls up
+/// once the cursor moves past the bottom. The following control sequences
+/// are understood:
+///
+///   * `ESC [ n m`: select graphic rendition. Supports reset (0), bold (1,
+///     shown as bright colours), normal (22), foreground colours (30-37,
+///     90-97, 39 for the default) and background colours (40-47, 100-107, 49
+///     for the default).
+///   * `ESC [ n J` and `ESC [ n K`: erase the display or line.
+///   * `ESC [ row ; column H`: move the cursor.
+///   * `ESC [ n A`, `B`, `C` and `D`: move the cursor up, down, forward or
+///     back.
+pub struct Screen {
+    framebuffer: Framebuffer,
+    /// The factor glyphs are scaled up by.
+    scale: u32,
+    /// The size of the screen in characters, never 0.
+    columns: u32,
+    rows: u32,
+    /// The cursor position. `column` may equal `columns`, in which case the
+    /// next printed character wraps to the next line.
+    column: u32,
+    row: u32,
+    foreground: u8,
+    background: u8,
+    bold: bool,
+    /// `PALETTE` as pixel values.
+    palette: [u32; 16],
+    parser: Parser,
+}
+
+impl Screen {
+    /// Returns a text console covering `framebuffer`, which is cleared.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::TooSmall` if not even one character fits.
+    pub fn new(framebuffer: Framebuffer) -> Result<Screen, Error> {
+        let scale = (framebuffer.width() / (TARGET_COLUMNS * font::WIDTH)).max(1);
+        let mut palette = [0; 16];
+        for (pixel, &(red, green, blue)) in palette.iter_mut().zip(PALETTE.iter()) {
+            *pixel = framebuffer.color(red, green, blue);
+        }
+
+        let columns = framebuffer.width() / (font::WIDTH * scale);
+        let rows = framebuffer.height() / (font::HEIGHT * scale);
+        if columns == 0 || rows == 0 {
+            return Err(Error::TooSmall);
+        }
+
+        let mut screen = Screen {
+            columns,
+            rows,
+            framebuffer,
+            scale,
+            column: 0,
+            row: 0,
+            foreground: DEFAULT_FOREGROUND,
+            background: DEFAULT_BACKGROUND,
+            bold: false,
+            palette,
+            parser: Parser::new(),
+        };
+
+        screen.clear();
+        Ok(screen)
+    }
+
+    /// The width of the screen in characters.
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// The height of the screen in characters.
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Clears the screen and moves the cursor to the top-left corner.
+    pub fn clear(&mut self) {
+        let background = self.palette[self.background as usize];
+        self.framebuffer.clear(background);
+        self.column = 0;
+        self.row = 0;
+    }
+
+    /// Writes `byte` to the screen.
+    pub fn write_byte(&mut self, byte: u8) {
+        match self.parser.feed(byte) {
+            Some(Action::Byte(byte)) => self.output(byte),
+            Some(action) => self.control(&action),
+            None => {}
+        }
+    }
+
+    /// Handles a byte outside of an escape sequence.
+    fn output(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.newline(),
+            b'\r' => self.column = 0,
+            0x08 => self.column = self.column.min(self.columns - 1).saturating_sub(1),
+            b'\t' => self.column = self.columns.min((self.column / TAB_WIDTH + 1) * TAB_WIDTH),
+            // Other control characters, such as the bell, aren't shown.
+            0x00...0x1F | 0x7F => {}
+            // UTF-8 continuation bytes; the leading byte was drawn as `?`.
+            0x80...0xBF => {}
+            _ => {
+                if self.column >= self.columns {
+                    self.newline();
+                }
+
+                let (column, row) = (self.column, self.row);
+                self.draw(column, row, byte);
+                self.column += 1;
+            }
+        }
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling if needed.
+    fn newline(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            let background = self.palette[self.background as usize];
+            let lines = font::HEIGHT * self.scale;
+            self.framebuffer.scroll_up(lines, background);
+        }
+    }
+
+    /// Returns the pixel values of the current foreground and background.
+    fn colors(&self) -> (u32, u32) {
+        let mut foreground = self.foreground;
+        if self.bold && foreground < 8 {
+            foreground += 8;
+        }
+
+        (self.palette[foreground as usize], self.palette[self.background as usize])
+    }
+
+    /// Draws `byte` in the cell at (`column`, `row`).
+    fn draw(&mut self, column: u32, row: u32, byte: u8) {
+        let (foreground, background) = self.colors();
+        let scale = self.scale;
+        let x = column * font::WIDTH * scale;
+        let y = row * font::HEIGHT * scale;
+        for (dy, &bits) in font::glyph(byte).iter().enumerate() {
+            for dx in 0..font::WIDTH {
+                let color = match bits & (1 << dx) {
+                    0 => background,
+                    _ => foreground,
+                };
+
+                let py = y + dy as u32 * scale;
+                self.framebuffer.fill_rect(x + dx * scale, py, scale, scale, color);
+            }
+        }
+    }
+
+    /// Fills the cells `start..end` of `row` with the background colour.
+    fn erase(&mut self, row: u32, start: u32, end: u32) {
+        let (_, background) = self.colors();
+        let (width, height) = (font::WIDTH * self.scale, font::HEIGHT * self.scale);
+        let columns = end.saturating_sub(start);
+        self.framebuffer
+            .fill_rect(start * width, row * height, columns * width, height, background);
+    }
+
+    /// Fills the rows `start..end` with the background colour.
+    fn erase_rows(&mut self, start: u32, end: u32) {
+        let columns = self.columns;
+        for row in start..end {
+            self.erase(row, 0, columns);
+        }
+    }
+
+    /// Performs the control sequence `action`.
+    fn control(&mut self, action: &Action) {
+        let command = match *action {
+            Action::Control { command, .. } => command,
+            Action::Byte(_) => return,
+        };
+
+        let (columns, rows) = (self.columns, self.rows);
+        let (column, row) = (self.column.min(columns - 1), self.row);
+        match command {
+            b'm' => self.select_rendition(action),
+            b'J' => match action.param(0, 0) {
+                0 => {
+                    self.erase(row, column, columns);
+                    self.erase_rows(row + 1, rows);
+                }
+                1 => {
+                    self.erase_rows(0, row);
+                    self.erase(row, 0, column + 1);
+                }
+                _ => self.erase_rows(0, rows),
+            },
+            b'K' => match action.param(0, 0) {
+                0 => self.erase(row, column, columns),
+                1 => self.erase(row, 0, column + 1),
+                _ => self.erase(row, 0, columns),
+            },
+            b'H' | b'f' => {
+                self.row = rows.min(action.param(0, 1)) - 1;
+                self.column = columns.min(action.param(1, 1)) - 1;
+            }
+            b'A' => self.row = row.saturating_sub(action.param(0, 1)),
+            b'B' => self.row = (rows - 1).min(row.saturating_add(action.param(0, 1))),
+            b'C' => self.column = (columns - 1).min(column.saturating_add(action.param(0, 1))),
+            b'D' => self.column = column.saturating_sub(action.param(0, 1)),
+            _ => {}
+        }
+    }
+
+    /// Performs the select graphic rendition control sequence `action`.
+    fn select_rendition(&mut self, action: &Action) {
+        let len = match *action {
+            Action::Control { len, .. } => len,
+            Action::Byte(_) => return,
+        };
+
+        for i in 0..len {
+            match action.param(i, 0) {
+                0 => {
+                    self.foreground = DEFAULT_FOREGROUND;
+                    self.background = DEFAULT_BACKGROUND;
+                    self.bold = false;
+                }
+                1 => self.bold = true,
+                22 => self.bold = false,
+                n @ 30...37 => self.foreground = (n - 30) as u8,
+                39 => self.foreground = DEFAULT_FOREGROUND,
+                n @ 40...47 => self.background = (n - 40) as u8,
+                49 => self.background = DEFAULT_BACKGROUND,
+                n @ 90...97 => self.foreground = (n - 90 + 8) as u8,
+                n @ 100...107 => self.background = (n - 100 + 8) as u8,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Write for Screen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+
+        Ok(())
+    }
+}
+
+/// The screen console output is mirrored to, if any.
+pub static SCREEN: IrqMutex<Option<Screen>> = IrqMutex::new(None);
+
+/// Allocates a framebuffer the size of the display and starts mirroring
+/// console output to it.
+///
+/// # Errors
+///
+/// Returns an error if the firmware can't allocate a framebuffer, for
+/// instance because no display is connected, or if the display is too small
+/// for text.
+pub fn initialize() -> Result<(), Error> {
+    let framebuffer = Framebuffer::new(0, 0)?;
+    *SCREEN.lock() = Some(Screen::new(framebuffer)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Action> {
+        let mut parser = Parser::new();
+        bytes.iter().filter_map(|&byte| parser.feed(byte)).collect()
+    }
+
+    fn control(command: u8, values: &[u32]) -> Action {
+        let mut params = [0; MAX_PARAMS];
+        params[..values.len()].copy_from_slice(values);
+        Action::Control {
+            command,
+            params,
+            len: values.len(),
+        }
+    }
+
+    #[test]
+    fn check_parser() {
+        assert_eq!(parse(b"a\n"), vec![Action::Byte(b'a'), Action::Byte(b'\n')]);
+        assert_eq!(parse(b"\x1b[m"), vec![control(b'm', &[0])]);
+        assert_eq!(parse(b"\x1b[1;31mx"), vec![control(b'm', &[1, 31]), Action::Byte(b'x')]);
+        assert_eq!(parse(b"\x1b[;5H"), vec![control(b'H', &[0, 5])]);
+        assert_eq!(parse(b"\x1b[?25l"), vec![control(b'l', &[25])]);
+        assert_eq!(parse(b"\x1b(Bz"), vec![Action::Byte(b'z')]);
+        assert_eq!(parse(b"\x1b[1;2;3;4;5;6m"), vec![control(b'm', &[1, 2, 3, 4])]);
+    }
+
+    #[test]
+    fn check_params() {
+        let action = control(b'H', &[0, 5]);
+        assert_eq!(action.param(0, 1), 1);
+        assert_eq!(action.param(1, 1), 5);
+        assert_eq!(action.param(2, 1), 1);
+        assert_eq!(Action::Byte(b'H').param(0, 7), 7);
+    }
+}
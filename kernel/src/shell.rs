@@ -1,9 +1,13 @@
-use console::{kprint, kprintln, CONSOLE};
+use console::{self, kprint, kprintln};
 use fat32::traits::{Dir, Entry, File, FileSystem, Metadata, Timestamp};
 use stack_vec::StackVec;
 use std::io::Read;
 use std::path::{Component, PathBuf};
 use std::str;
+use boot;
+use pi::interrupt::{Interrupt, NUM_INTERRUPTS};
+use pi::mailbox::{self, GetBoardRevision, GetBoardSerial};
+use traps::irq;
 use FILE_SYSTEM;
 
 /// Error type for `Command` parse failures.
@@ -49,8 +53,10 @@ impl<'a> Command<'a> {
             "cat" => cmd_cat(&self.args[1..], cwd),
             "cd" => cmd_cd(&self.args[1..], cwd),
             "echo" => cmd_echo(&self.args[1..]),
+            "irqs" => cmd_irqs(&self.args[1..]),
             "ls" => cmd_ls(&self.args[1..], cwd),
             "pwd" => cmd_pwd(&self.args[1..], cwd),
+            "uname" => cmd_uname(&self.args[1..]),
             "reset" => {
                 kprintln!("goodbye!");
                 kprintln!("press `<ctrl-a>`, `k` to exit");
@@ -127,6 +133,21 @@ pub fn cmd_echo(args: &[&str]) {
     kprintln!();
 }
 
+pub fn cmd_irqs(args: &[&str]) {
+    if args.len() > 0 {
+        kprintln!("irqs: too many arguments");
+        return;
+    }
+
+    for index in 0..NUM_INTERRUPTS as u32 {
+        let int = Interrupt::from_index(index).unwrap();
+        let count = irq::count(int);
+        if count > 0 {
+            kprintln!("{:>3} {:>10} {:?}", index, count, int);
+        }
+    }
+}
+
 pub fn cmd_ls(args: &[&str], cwd: &PathBuf) {
     let mut show_hidden = false;
     let mut directory: PathBuf = cwd.clone();
@@ -206,6 +227,27 @@ pub fn cmd_pwd(args: &[&str], cwd: &PathBuf) {
     kprintln!("{}", cwd.display());
 }
 
+pub fn cmd_uname(args: &[&str]) {
+    if args.len() > 0 {
+        kprintln!("uname: too many arguments");
+        return;
+    }
+
+    let info = boot::info();
+    kprint!("{} {} aarch64", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let revision = info.revision().or_else(|| mailbox::request(&GetBoardRevision).ok());
+    let serial = info.serial().or_else(|| mailbox::request(&GetBoardSerial).ok());
+    match revision {
+        Some(revision) => kprint!(" revision {:06x}", revision),
+        None => kprint!(" revision unknown"),
+    }
+    match serial {
+        Some(serial) => kprint!(" serial {:016x}", serial),
+        None => kprint!(" serial unknown"),
+    }
+    kprintln!();
+}
+
 pub fn path_normalize(path: &PathBuf) -> PathBuf {
     let mut norm = PathBuf::new();
     for component in path.components() {
@@ -228,7 +270,7 @@ const DEL: u8 = 0x7F;
 
 fn read_line(mut line_vec: StackVec<u8>) -> &str {
     loop {
-        let byte = CONSOLE.lock().read_byte();
+        let byte = console::read_byte();
         match byte {
             // Printable characters
             byte @ 0x20...0x7E => match line_vec.push(byte) {
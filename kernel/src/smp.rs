@@ -0,0 +1,41 @@
+use std::ptr;
+
+/// The number of cores.
+pub const NUM_CORES: usize = 4;
+
+/// The spin table: core `n` waits for an entry address at `SPIN_TABLE + 8 * n`.
+/// Matches `SPIN_TABLE` in `init.S`.
+const SPIN_TABLE: usize = 0xd8;
+
+/// The size of each core's EL1 stack. Core `n`'s stack ends `n * STACK_SIZE`
+/// bytes below `_start`. Matches `CORE_STACK_SIZE` in `init.S`.
+pub const STACK_SIZE: usize = 0x10000;
+
+extern "C" {
+    static _start: u8;
+    fn _start_secondary();
+}
+
+/// Returns the address the EL1 stack of `core` starts at, growing down.
+pub fn stack_top(core: usize) -> usize {
+    unsafe { &_start as *const u8 as usize - core * STACK_SIZE }
+}
+
+/// Releases cores 1 through 3 from their spin loops, in the firmware's stub
+/// or in `_start`. They set up their stacks and EL1 and call
+/// `kmain_secondary()`.
+pub fn start_secondary_cores() {
+    for core in 1..NUM_CORES {
+        let slot = (SPIN_TABLE + core * 8) as *mut u64;
+        unsafe { ptr::write_volatile(slot, _start_secondary as u64) };
+
+        // The waiting cores read memory with their MMU and caches off.
+        unsafe { asm!("dc civac, $0" :: "r"(slot) : "memory" : "volatile") };
+    }
+
+    unsafe {
+        asm!("dsb sy
+              sev"
+             :::: "volatile");
+    }
+}
@@ -0,0 +1,315 @@
+// Synchronization primitives for code running in a process. Unlike
+// `mutex::Mutex`, a process that can't take one right away sleeps: the
+// system calls in `syscall` put it in `State::Waiting` until the resource is
+// free, so it doesn't spin through its time slices.
+//
+// None of these may be used by interrupt handlers. In an exception handler,
+// where system calls can't be made, they panic rather than wait: spinning
+// there, with interrupts masked, would never see the resource freed.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut, Drop};
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+use syscall;
+use traps;
+
+/// The lock of a `Mutex`, without the data it protects. The `lock` system
+/// call takes one of these.
+pub struct RawMutex {
+    locked: AtomicBool,
+}
+
+impl RawMutex {
+    pub const fn new() -> RawMutex {
+        RawMutex { locked: AtomicBool::new(false) }
+    }
+
+    /// Takes the lock if it's free. Returns `true` if it was taken.
+    pub fn try_acquire(&self) -> bool {
+        self.locked.compare_exchange(false, true, Acquire, Relaxed).is_ok()
+    }
+
+    /// Takes the lock, sleeping until it's free.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is held and this is called in an exception handler.
+    pub fn acquire(&self) {
+        if self.try_acquire() {
+            return;
+        }
+
+        match traps::in_exception() {
+            false => syscall::lock(self),
+            true => panic!("sync: waiting for a mutex in an exception handler"),
+        }
+    }
+
+    /// Frees the lock. The processes waiting for it try to take it on their
+    /// next turn.
+    pub fn release(&self) {
+        self.locked.store(false, Release);
+    }
+}
+
+/// A mutual exclusion lock that puts the processes waiting for it to sleep.
+pub struct Mutex<T> {
+    raw: RawMutex,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+pub struct MutexGuard<'a, T: 'a> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<'a, T> !Send for MutexGuard<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(val: T) -> Mutex<T> {
+        Mutex {
+            raw: RawMutex::new(),
+            data: UnsafeCell::new(val),
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        match self.raw.try_acquire() {
+            true => Some(MutexGuard { mutex: self }),
+            false => None,
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        self.raw.acquire();
+        MutexGuard { mutex: self }
+    }
+}
+
+impl<'a, T: 'a> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: 'a> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: 'a> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.raw.release()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f.debug_struct("Mutex").field("data", &&*guard).finish(),
+            None => f.debug_struct("Mutex").field("data", &"<locked>").finish(),
+        }
+    }
+}
+
+/// A counting semaphore. Processes acquiring it while its count is 0 sleep
+/// until it's released.
+#[derive(Debug)]
+pub struct Semaphore {
+    count: AtomicUsize,
+}
+
+impl Semaphore {
+    /// Returns a semaphore that can be acquired `count` times before it's
+    /// released.
+    pub const fn new(count: usize) -> Semaphore {
+        Semaphore { count: AtomicUsize::new(count) }
+    }
+
+    /// Decrements the count if it's above 0. Returns `true` if it was
+    /// decremented.
+    pub fn try_acquire(&self) -> bool {
+        let mut count = self.count.load(Relaxed);
+        while count > 0 {
+            match self.count.compare_exchange(count, count - 1, Acquire, Relaxed) {
+                Ok(_) => return true,
+                Err(actual) => count = actual,
+            }
+        }
+
+        false
+    }
+
+    /// Decrements the count, sleeping until it's above 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the count is 0 and this is called in an exception handler.
+    pub fn acquire(&self) {
+        if self.try_acquire() {
+            return;
+        }
+
+        match traps::in_exception() {
+            false => syscall::semaphore_acquire(self),
+            true => panic!("sync: waiting for a semaphore in an exception handler"),
+        }
+    }
+
+    /// Increments the count. One of the processes waiting for the semaphore,
+    /// if any, acquires it on its next turn.
+    pub fn release(&self) {
+        self.count.fetch_add(1, Release);
+    }
+
+    /// Returns the current count.
+    pub fn count(&self) -> usize {
+        self.count.load(Relaxed)
+    }
+}
+
+/// A condition variable. Processes wait on it with a `Mutex` locked and sleep
+/// until they're notified.
+///
+/// Each notification hands out a wakeup that one waiting process takes. As
+/// with any condition variable, a process may wake without the condition it
+/// waits for being true, so it must check the condition again.
+#[derive(Debug)]
+pub struct Condvar {
+    /// The number of waiting processes that haven't taken a wakeup yet.
+    waiters: AtomicUsize,
+    /// The number of wakeups not taken yet.
+    wakeups: AtomicUsize,
+}
+
+impl Condvar {
+    pub const fn new() -> Condvar {
+        Condvar {
+            waiters: AtomicUsize::new(0),
+            wakeups: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes a wakeup if there is one. Returns `true` if one was taken.
+    pub fn try_wake(&self) -> bool {
+        let mut wakeups = self.wakeups.load(Relaxed);
+        while wakeups > 0 {
+            match self.wakeups.compare_exchange(wakeups, wakeups - 1, Acquire, Relaxed) {
+                Ok(_) => {
+                    self.waiters.fetch_sub(1, Relaxed);
+                    return true;
+                }
+                Err(actual) => wakeups = actual,
+            }
+        }
+
+        false
+    }
+
+    /// Unlocks the mutex `guard` holds and sleeps until the condition
+    /// variable is notified, then locks the mutex again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no wakeup to take and this is called in an
+    /// exception handler.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        // Waiting is recorded before the mutex is unlocked, so a notification
+        // sent once it's unlocked reaches this process.
+        self.waiters.fetch_add(1, Relaxed);
+        let mutex = guard.mutex;
+        drop(guard);
+
+        if !self.try_wake() {
+            match traps::in_exception() {
+                false => syscall::condvar_wait(self),
+                true => panic!("sync: waiting on a condition variable in an exception handler"),
+            }
+        }
+
+        mutex.lock()
+    }
+
+    /// Hands out up to `count` more wakeups, no more than there are waiting
+    /// processes without one.
+    fn notify(&self, count: usize) {
+        let mut wakeups = self.wakeups.load(Relaxed);
+        loop {
+            let target = wakeups.saturating_add(count).min(self.waiters.load(Relaxed));
+            if wakeups >= target {
+                return;
+            }
+
+            match self.wakeups.compare_exchange(wakeups, target, Release, Relaxed) {
+                Ok(_) => return,
+                Err(actual) => wakeups = actual,
+            }
+        }
+    }
+
+    /// Wakes one of the processes waiting on the condition variable, if any.
+    pub fn notify_one(&self) {
+        self.notify(1);
+    }
+
+    /// Wakes all processes waiting on the condition variable.
+    pub fn notify_all(&self) {
+        self.notify(usize::max_value());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_mutex() {
+        let mutex = Mutex::new(1);
+        {
+            let mut guard = mutex.try_lock().unwrap();
+            *guard += 1;
+            assert!(mutex.try_lock().is_none());
+        }
+
+        assert_eq!(*mutex.try_lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn check_semaphore() {
+        let semaphore = Semaphore::new(2);
+        assert!(semaphore.try_acquire());
+        assert!(semaphore.try_acquire());
+        assert!(!semaphore.try_acquire());
+
+        semaphore.release();
+        assert_eq!(semaphore.count(), 1);
+        assert!(semaphore.try_acquire());
+        assert!(!semaphore.try_acquire());
+    }
+
+    #[test]
+    fn check_condvar() {
+        let condvar = Condvar::new();
+        condvar.notify_one();
+        assert!(!condvar.try_wake(), "notified without waiters");
+
+        condvar.waiters.fetch_add(3, Relaxed);
+        condvar.notify_one();
+        assert!(condvar.try_wake());
+        assert!(!condvar.try_wake());
+
+        condvar.notify_all();
+        assert!(condvar.try_wake());
+        assert!(condvar.try_wake());
+        assert!(!condvar.try_wake());
+    }
+}
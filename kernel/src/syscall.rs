@@ -0,0 +1,97 @@
+// Wrappers for the system calls handled in `traps::syscall`, for use by code
+// running in a process. The `svc` immediates are the `SYS_*` numbers there.
+
+use sync::{Condvar, RawMutex, Semaphore};
+use traps::syscall::STATUS_OK;
+
+/// Panics unless the system call `name` returned `STATUS_OK` in `x7`. The
+/// wrappers only pass valid arguments, so a failure is a kernel bug, and
+/// returning would let callers act as if they held a lock they never took.
+fn check(name: &str, status: u64) {
+    if status != STATUS_OK {
+        panic!("syscall: {} failed with status {}", name, status);
+    }
+}
+
+/// Sleeps for at least `ms` milliseconds. Returns the time that actually
+/// elapsed in milliseconds.
+pub fn sleep(ms: u32) -> u32 {
+    let elapsed: u64;
+    let status: u64;
+    unsafe {
+        asm!("mov x0, $2
+              svc 1
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(elapsed), "=r"(status)
+             : "r"(ms as u64)
+             : "x0", "x7", "memory"
+             : "volatile");
+    }
+
+    check("sleep", status);
+    elapsed as u32
+}
+
+/// Blocks the calling process until the console has a byte to read.
+pub fn wait_input() {
+    let status: u64;
+    unsafe {
+        asm!("svc 2
+              mov $0, x7"
+             : "=r"(status)
+             :
+             : "x7", "memory"
+             : "volatile");
+    }
+
+    check("wait_input", status);
+}
+
+/// Blocks the calling process until it takes `mutex`.
+pub fn lock(mutex: &RawMutex) {
+    let status: u64;
+    unsafe {
+        asm!("mov x0, $1
+              svc 3
+              mov $0, x7"
+             : "=r"(status)
+             : "r"(mutex as *const RawMutex as u64)
+             : "x0", "x7", "memory"
+             : "volatile");
+    }
+
+    check("lock", status);
+}
+
+/// Blocks the calling process until it decrements `semaphore`'s count.
+pub fn semaphore_acquire(semaphore: &Semaphore) {
+    let status: u64;
+    unsafe {
+        asm!("mov x0, $1
+              svc 4
+              mov $0, x7"
+             : "=r"(status)
+             : "r"(semaphore as *const Semaphore as u64)
+             : "x0", "x7", "memory"
+             : "volatile");
+    }
+
+    check("semaphore_acquire", status);
+}
+
+/// Blocks the calling process until it takes a wakeup of `condvar`.
+pub fn condvar_wait(condvar: &Condvar) {
+    let status: u64;
+    unsafe {
+        asm!("mov x0, $1
+              svc 5
+              mov $0, x7"
+             : "=r"(status)
+             : "r"(condvar as *const Condvar as u64)
+             : "x0", "x7", "memory"
+             : "volatile");
+    }
+
+    check("condvar_wait", status);
+}
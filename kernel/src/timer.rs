@@ -0,0 +1,249 @@
+use std::cmp;
+
+use pi::timer::{current_time, Channel, Timer};
+
+use mutex::IrqMutex;
+use traps::irq;
+
+/// The compare channel all software timers share. Channel 1 is left free.
+const CHANNEL: Channel = Channel::Three;
+
+/// The least number of microseconds a match is set up ahead of the counter,
+/// so the counter can't pass it before the compare value is written.
+const MIN_DELAY_US: u64 = 10;
+
+/// A function called from the IRQ handler when its timer expires.
+type Callback = Box<FnMut() + Send>;
+
+/// Identifies a timer, to cancel it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimerId(u64);
+
+/// A pending timer.
+struct Entry {
+    id: TimerId,
+    /// The time the timer expires at, in microseconds.
+    deadline: u64,
+    /// The interval a periodic timer expires at, in microseconds.
+    period: Option<u64>,
+    callback: Callback,
+}
+
+/// The pending timers, sorted by deadline.
+struct Queue {
+    entries: Vec<Entry>,
+    next_id: u64,
+    /// The periodic timer whose callback is running, unless it was cancelled
+    /// by it.
+    running: Option<TimerId>,
+}
+
+impl Queue {
+    fn new() -> Queue {
+        Queue {
+            entries: Vec::new(),
+            next_id: 0,
+            running: None,
+        }
+    }
+
+    /// Adds a timer expiring at `deadline` and returns its ID.
+    fn insert(&mut self, deadline: u64, period: Option<u64>, callback: Callback) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.push(Entry { id, deadline, period, callback });
+        id
+    }
+
+    /// Adds `entry` after the timers expiring before or with it, so timers
+    /// with the same deadline expire in the order they were added.
+    fn push(&mut self, entry: Entry) {
+        let index = self.entries
+            .iter()
+            .position(|e| e.deadline > entry.deadline)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+    }
+
+    /// Removes the timer `id`. Returns `false` if it already expired or was
+    /// cancelled.
+    fn cancel(&mut self, id: TimerId) -> bool {
+        if self.running == Some(id) {
+            self.running = None;
+            return true;
+        }
+
+        match self.entries.iter().position(|e| e.id == id) {
+            Some(index) => {
+                self.entries.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns the first timer if it expired at `now`.
+    fn pop_due(&mut self, now: u64) -> Option<Entry> {
+        if self.entries.first()?.deadline > now {
+            return None;
+        }
+
+        Some(self.entries.remove(0))
+    }
+
+    /// Returns the deadline of the first timer, if any.
+    fn next_deadline(&self) -> Option<u64> {
+        self.entries.first().map(|e| e.deadline)
+    }
+}
+
+static QUEUE: IrqMutex<Option<Queue>> = IrqMutex::new(None);
+
+/// Sets up the timer queue and registers the IRQ handler of the compare
+/// channel it uses.
+pub fn initialize() {
+    *QUEUE.lock() = Some(Queue::new());
+    irq::register(CHANNEL.interrupt(), |_, _| handle_irq());
+}
+
+/// Sets up the match for the first timer in `queue`, if any.
+fn arm(queue: &Queue) {
+    if let Some(deadline) = queue.next_deadline() {
+        let time = cmp::max(deadline, current_time() + MIN_DELAY_US);
+        Timer::new().set_compare(CHANNEL, time as u32);
+    }
+}
+
+/// Adds a timer to the queue and sets up the match for the first one.
+fn add(us: u64, period: Option<u64>, callback: Callback) -> TimerId {
+    let mut guard = QUEUE.lock();
+    let queue = guard.as_mut().expect("timer queue uninitialized");
+    let id = queue.insert(current_time() + us, period, callback);
+    arm(queue);
+    id
+}
+
+/// Calls `callback` once, from the IRQ handler, `us` microseconds from now.
+///
+/// # Panics
+///
+/// Panics if the timer queue is uninitialized.
+pub fn after<F: FnMut() + Send + 'static>(us: u64, callback: F) -> TimerId {
+    add(us, None, Box::new(callback))
+}
+
+/// Calls `callback` from the IRQ handler every `us` microseconds, starting
+/// `us` microseconds from now, until the timer is cancelled. A call that is
+/// late by more than a period delays the following ones rather than being
+/// repeated to catch up.
+///
+/// # Panics
+///
+/// Panics if `us` is 0 or if the timer queue is uninitialized.
+pub fn every<F: FnMut() + Send + 'static>(us: u64, callback: F) -> TimerId {
+    assert!(us > 0, "timer: periodic timer with a period of 0");
+    add(us, Some(us), Box::new(callback))
+}
+
+/// Cancels the timer `id`. A periodic timer may cancel itself from its
+/// callback. Returns `false` if the timer already expired or was cancelled.
+pub fn cancel(id: TimerId) -> bool {
+    match QUEUE.lock().as_mut() {
+        Some(queue) => queue.cancel(id),
+        None => false,
+    }
+}
+
+/// Calls the callbacks of the expired timers and sets up the match for the
+/// next one. Called when the compare channel's interrupt is pending.
+pub fn handle_irq() {
+    Timer::new().clear_match(CHANNEL);
+
+    loop {
+        // The lock isn't held while a callback runs, so callbacks may add
+        // and cancel timers.
+        let mut entry = {
+            let mut guard = QUEUE.lock();
+            let queue = match guard.as_mut() {
+                Some(queue) => queue,
+                None => return,
+            };
+
+            match queue.pop_due(current_time()) {
+                Some(entry) => {
+                    if entry.period.is_some() {
+                        queue.running = Some(entry.id);
+                    }
+                    entry
+                }
+                None => {
+                    arm(queue);
+                    return;
+                }
+            }
+        };
+
+        (entry.callback)();
+
+        if let Some(period) = entry.period {
+            let mut guard = QUEUE.lock();
+            let queue = guard.as_mut().expect("timer queue uninitialized");
+            if queue.running == Some(entry.id) {
+                queue.running = None;
+                entry.deadline = cmp::max(entry.deadline + period, current_time());
+                queue.push(entry);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(queue: &mut Queue, deadline: u64) -> TimerId {
+        queue.insert(deadline, None, Box::new(|| {}))
+    }
+
+    fn due(queue: &mut Queue, now: u64) -> Vec<TimerId> {
+        let mut ids = vec![];
+        while let Some(entry) = queue.pop_due(now) {
+            ids.push(entry.id);
+        }
+        ids
+    }
+
+    #[test]
+    fn check_order() {
+        let mut queue = Queue::new();
+        let late = insert(&mut queue, 300);
+        let early = insert(&mut queue, 100);
+        let first = insert(&mut queue, 200);
+        let second = insert(&mut queue, 200);
+
+        assert_eq!(queue.next_deadline(), Some(100));
+        assert_eq!(due(&mut queue, 99), vec![]);
+        assert_eq!(due(&mut queue, 100), vec![early]);
+        assert_eq!(due(&mut queue, 250), vec![first, second]);
+        assert_eq!(due(&mut queue, 1000), vec![late]);
+        assert_eq!(queue.next_deadline(), None);
+    }
+
+    #[test]
+    fn check_cancel() {
+        let mut queue = Queue::new();
+        let a = insert(&mut queue, 100);
+        let b = insert(&mut queue, 200);
+
+        assert!(queue.cancel(a));
+        assert!(!queue.cancel(a));
+        assert_eq!(queue.next_deadline(), Some(200));
+
+        // A running periodic timer is out of the queue until it's pushed back.
+        let entry = queue.pop_due(200).unwrap();
+        queue.running = Some(entry.id);
+        assert!(queue.cancel(b));
+        assert_eq!(queue.running, None);
+        assert!(!queue.cancel(b));
+    }
+}
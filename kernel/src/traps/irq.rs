@@ -1,12 +1,71 @@
-use console::kprintln;
-use pi::interrupt::Interrupt;
-use pi::timer::tick_in;
-use process::TICK;
+use aarch64::timer::tick_in;
+use console::{klog, LogLevel};
+use mutex::IrqMutex;
+use pi::interrupt::{Controller, Interrupt, NUM_INTERRUPTS};
+use process::{tick, State};
 use traps::TrapFrame;
+use SCHEDULER;
 
-pub fn handle_irq(interrupt: Interrupt, tf: &mut TrapFrame) {
-    if interrupt == Interrupt::Timer1 {
-        kprintln!("[tick]");
-        tick_in(TICK);
+/// A function called from the IRQ handler with the pending interrupt it was
+/// registered for.
+pub type Handler = fn(Interrupt, &mut TrapFrame);
+
+/// The handler registered for an interrupt and the number of times the
+/// interrupt was handled.
+#[derive(Copy, Clone)]
+struct Entry {
+    handler: Option<Handler>,
+    count: u64,
+}
+
+static ENTRIES: IrqMutex<[Entry; NUM_INTERRUPTS]> =
+    IrqMutex::new([Entry { handler: None, count: 0 }; NUM_INTERRUPTS]);
+
+/// Calls `handler` from the IRQ handler whenever `int` is pending, replacing
+/// any handler registered before, and enables `int`. The handler must clear
+/// the interrupt's cause, or it is called again right away.
+pub fn register(int: Interrupt, handler: Handler) {
+    ENTRIES.lock()[int as usize].handler = Some(handler);
+    Controller::new().enable(int);
+}
+
+/// Disables `int` and removes the handler registered for it.
+pub fn unregister(int: Interrupt) {
+    Controller::new().disable(int);
+    ENTRIES.lock()[int as usize].handler = None;
+}
+
+/// Returns the number of times `int` was pending when IRQs were handled.
+pub fn count(int: Interrupt) -> u64 {
+    ENTRIES.lock()[int as usize].count
+}
+
+/// Handles this core's generic timer interrupt: the scheduler's tick.
+pub fn handle_tick(tf: &mut TrapFrame) {
+    klog!(LogLevel::Debug, "[tick]");
+    tick_in(tick());
+    let _ = SCHEDULER.switch(State::Ready, tf);
+}
+
+/// Calls the handlers of the pending interrupts in order of their numbers.
+/// Interrupts without a handler are disabled.
+pub fn handle_irqs(tf: &mut TrapFrame) {
+    for int in Controller::new().pending() {
+        // The lock isn't held while a handler runs, so handlers may register
+        // and unregister handlers.
+        let handler = {
+            let mut entries = ENTRIES.lock();
+            let entry = &mut entries[int as usize];
+            entry.count += 1;
+            entry.handler
+        };
+
+        match handler {
+            Some(handler) => handler(int, tf),
+            None => {
+                klog!(LogLevel::Warn, "Unhandled interrupt {:?}; disabling it", int);
+                Controller::new().disable(int);
+            }
+        }
     }
 }
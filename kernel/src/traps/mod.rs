@@ -1,18 +1,22 @@
-mod irq;
+pub mod fiq;
+pub mod irq;
 mod syndrome;
-mod syscall;
+pub mod syscall;
 mod trap_frame;
 
-use pi::interrupt::{Controller, Interrupt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pi::interrupt::{LocalController, LocalInterrupt};
 
 pub use self::trap_frame::TrapFrame;
 
-use self::irq::handle_irq;
+use self::irq::{handle_irqs, handle_tick};
 use self::syndrome::Syndrome;
 use self::syscall::handle_syscall;
 use aarch64;
-use console::kprintln;
+use console::{klog, LogLevel};
 use shell;
+use smp::NUM_CORES;
 
 #[repr(u16)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -39,15 +43,49 @@ pub struct Info {
     kind: Kind,
 }
 
+/// The number of exceptions each core is currently handling. Only loads and
+/// stores are used: only the core itself updates its depth, with exceptions
+/// masked.
+static DEPTH: [AtomicUsize; NUM_CORES] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// Returns this core's exception depth.
+fn depth() -> &'static AtomicUsize {
+    &DEPTH[aarch64::affinity()]
+}
+
+/// Returns `true` if the caller is running in an exception handler rather
+/// than in a process.
+pub fn in_exception() -> bool {
+    depth().load(Ordering::Relaxed) > 0
+}
+
+/// This function is called when an FIQ occurs, in place of
+/// `handle_exception()`. Only the registers a call may clobber are saved.
+#[no_mangle]
+pub extern "C" fn handle_fiq() {
+    let depth = depth();
+    depth.store(depth.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    fiq::handle_fiq();
+    depth.store(depth.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
+}
+
 /// This function is called when an exception occurs. The `info` parameter
 /// specifies the source and kind of exception that has occurred. The `esr` is
 /// the value of the exception syndrome register. Finally, `tf` is a pointer to
 /// the trap frame for the exception.
 #[no_mangle]
 pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
-    kprintln!("Exception: {:?}", info);
+    let depth = depth();
+    depth.store(depth.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+
+    klog!(LogLevel::Debug, "Exception: {:?}", info);
     if info.kind == Kind::Synchronous {
-        kprintln!("Syndrome: {:?}", Syndrome::from(esr));
+        klog!(LogLevel::Debug, "Syndrome: {:?}", Syndrome::from(esr));
     }
 
     if info.kind == Kind::Synchronous {
@@ -57,24 +95,20 @@ pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
                 shell::shell("brk> ");
                 tf.elr += 4;
             }
+            Syndrome::Svc(num) => handle_syscall(num, tf),
             _ => (),
         }
     } else if info.kind == Kind::Irq {
-        let controller = Controller::new();
-        let ints = [
-            Interrupt::Timer1,
-            Interrupt::Timer3,
-            Interrupt::Usb,
-            Interrupt::Gpio0,
-            Interrupt::Gpio1,
-            Interrupt::Gpio2,
-            Interrupt::Gpio3,
-            Interrupt::Uart,
-        ];
-        for int in ints.iter() {
-            if controller.is_pending(*int) {
-                handle_irq(*int, tf);
-            }
+        let core = aarch64::affinity();
+        let local = LocalController::new(core);
+        if local.is_pending(LocalInterrupt::PhysicalTimer) {
+            handle_tick(tf);
+        }
+
+        if local.is_pending(LocalInterrupt::Gpu) {
+            handle_irqs(tf);
         }
     }
+
+    depth.store(depth.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
 }
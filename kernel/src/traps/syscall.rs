@@ -1,4 +1,40 @@
+use std::mem;
+
+use console::CONSOLE;
+use pi::timer::current_time;
+use process::{Process, State};
+use sync::{Condvar, RawMutex, Semaphore};
 use traps::TrapFrame;
+use SCHEDULER;
+
+/// The number of the `sleep` system call.
+pub const SYS_SLEEP: u16 = 1;
+
+/// The number of the `wait_input` system call.
+pub const SYS_WAIT_INPUT: u16 = 2;
+
+/// The number of the `lock` system call.
+pub const SYS_LOCK: u16 = 3;
+
+/// The number of the `semaphore_acquire` system call.
+pub const SYS_SEMAPHORE_ACQUIRE: u16 = 4;
+
+/// The number of the `condvar_wait` system call.
+pub const SYS_CONDVAR_WAIT: u16 = 5;
+
+/// The status value for a successful system call.
+pub const STATUS_OK: u64 = 0;
+
+/// The status value for an unknown system call number.
+pub const STATUS_NO_SYSCALL: u64 = 1;
+
+/// The status value for an object address that is null or misaligned.
+pub const STATUS_BAD_ADDRESS: u64 = 2;
+
+/// Sets the status value of a system call, which is returned in `x7`.
+fn set_status(tf: &mut TrapFrame, status: u64) {
+    tf.x1_29[6] = status;
+}
 
 /// Sleep for `ms` milliseconds.
 ///
@@ -8,9 +44,144 @@ use traps::TrapFrame;
 /// parameter: the approximate true elapsed time from when `sleep` was called to
 /// when `sleep` returned.
 pub fn sleep(ms: u32, tf: &mut TrapFrame) {
-    unimplemented!("syscall: sleep()")
+    let start = current_time();
+    let end = start + ms as u64 * 1000;
+
+    let event_poll = Box::new(move |process: &mut Process| {
+        let now = current_time();
+        if now < end {
+            return false;
+        }
+
+        process.trap_frame.x0 = (now - start) / 1000;
+        set_status(&mut process.trap_frame, STATUS_OK);
+        true
+    });
+
+    let _ = SCHEDULER.switch(State::Waiting(event_poll), tf);
+}
+
+/// Wait until the console has a byte to read.
+///
+/// This system call takes no parameters and returns only the usual status
+/// value. It lets processes wait for input without holding the console lock.
+pub fn wait_input(tf: &mut TrapFrame) {
+    let event_poll = Box::new(|process: &mut Process| {
+        // The scheduler polls with `SCHEDULER` held, and a core holding
+        // `CONSOLE` may be waiting for `SCHEDULER`, so the console is never
+        // waited for here. If it's busy, it's checked again on the next turn.
+        let has_byte = match CONSOLE.try_lock() {
+            Some(mut console) => console.has_byte(),
+            None => false,
+        };
+
+        if !has_byte {
+            return false;
+        }
+
+        set_status(&mut process.trap_frame, STATUS_OK);
+        true
+    });
+
+    let _ = SCHEDULER.switch(State::Waiting(event_poll), tf);
+}
+
+/// Blocks the process until `ready` returns `true`. It's called right away,
+/// so the process keeps running if it does, and then on each of the process's
+/// turns.
+fn wait_until<F: FnMut() -> bool + Send + 'static>(mut ready: F, tf: &mut TrapFrame) {
+    if ready() {
+        set_status(tf, STATUS_OK);
+        return;
+    }
+
+    let event_poll = Box::new(move |process: &mut Process| {
+        if !ready() {
+            return false;
+        }
+
+        set_status(&mut process.trap_frame, STATUS_OK);
+        true
+    });
+
+    let _ = SCHEDULER.switch(State::Waiting(event_poll), tf);
+}
+
+/// Take a `sync::RawMutex`.
+///
+/// This system call takes one parameter: the address of the mutex. It
+/// returns only the usual status value, once the process holds the mutex.
+pub fn lock(mutex: &'static RawMutex, tf: &mut TrapFrame) {
+    wait_until(move || mutex.try_acquire(), tf);
+}
+
+/// Decrement the count of a `sync::Semaphore`.
+///
+/// This system call takes one parameter: the address of the semaphore. It
+/// returns only the usual status value, once the count was decremented.
+pub fn semaphore_acquire(semaphore: &'static Semaphore, tf: &mut TrapFrame) {
+    wait_until(move || semaphore.try_acquire(), tf);
+}
+
+/// Wait for a `sync::Condvar` to be notified.
+///
+/// This system call takes one parameter: the address of the condition
+/// variable. It returns only the usual status value, once the process took
+/// one of its wakeups.
+pub fn condvar_wait(condvar: &'static Condvar, tf: &mut TrapFrame) {
+    wait_until(move || condvar.try_wake(), tf);
+}
+
+/// Returns the object at `address`, a system call parameter, or `None` if
+/// `address` is null or not aligned for a `T`.
+///
+/// # Safety
+///
+/// Processes share the kernel's address space, so the wrappers in `syscall`
+/// pass references. The object must stay in place until the system call
+/// returns, which borrowing it for the call guarantees.
+unsafe fn object<T>(address: u64) -> Option<&'static T> {
+    let address = address as usize;
+    if address == 0 || address % mem::align_of::<T>() != 0 {
+        return None;
+    }
+
+    Some(&*(address as *const T))
 }
 
 pub fn handle_syscall(num: u16, tf: &mut TrapFrame) {
-    unimplemented!("handle_syscall()")
+    match num {
+        SYS_SLEEP => sleep(tf.x0 as u32, tf),
+        SYS_WAIT_INPUT => wait_input(tf),
+        SYS_LOCK => match unsafe { object(tf.x0) } {
+            Some(mutex) => lock(mutex, tf),
+            None => set_status(tf, STATUS_BAD_ADDRESS),
+        },
+        SYS_SEMAPHORE_ACQUIRE => match unsafe { object(tf.x0) } {
+            Some(semaphore) => semaphore_acquire(semaphore, tf),
+            None => set_status(tf, STATUS_BAD_ADDRESS),
+        },
+        SYS_CONDVAR_WAIT => match unsafe { object(tf.x0) } {
+            Some(condvar) => condvar_wait(condvar, tf),
+            None => set_status(tf, STATUS_BAD_ADDRESS),
+        },
+        _ => set_status(tf, STATUS_NO_SYSCALL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_object() {
+        let semaphore = Semaphore::new(1);
+        let address = &semaphore as *const Semaphore as u64;
+
+        unsafe {
+            assert!(object::<Semaphore>(0).is_none(), "null address");
+            assert!(object::<Semaphore>(address + 1).is_none(), "misaligned address");
+            assert_eq!(object::<Semaphore>(address).map(|s| s.count()), Some(1));
+        }
+    }
 }
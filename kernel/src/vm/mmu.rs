@@ -0,0 +1,192 @@
+use pi::common::IO_BASE;
+
+use boot::BootInfo;
+
+/// The number of entries in a translation table with a 4 KiB granule.
+const ENTRIES: usize = 512;
+
+/// The size of a page, mapped by a level 3 descriptor.
+const PAGE_SIZE: usize = 1 << 12;
+
+/// The size of a block mapped by a level 2 descriptor.
+const BLOCK_SIZE: usize = 1 << 21;
+
+/// The number of level 3 tables available to map the kernel's code page by
+/// page. The code must fit in this many blocks.
+const CODE_TABLES: usize = 4;
+
+/// A translation table.
+#[repr(C)]
+#[repr(align(4096))]
+struct Table([u64; ENTRIES]);
+
+/// The level 1 table. Each entry maps 1 GiB; with a 32-bit address space,
+/// only the first four are used.
+static mut L1: Table = Table([0; ENTRIES]);
+
+/// The level 2 table of the first GiB, which holds RAM and the peripherals.
+static mut L2: Table = Table([0; ENTRIES]);
+
+/// The level 3 tables of the blocks holding the kernel's code.
+static mut L3: [Table; CODE_TABLES] = [
+    Table([0; ENTRIES]),
+    Table([0; ENTRIES]),
+    Table([0; ENTRIES]),
+    Table([0; ENTRIES]),
+];
+
+/// Bits of table, block and page descriptors (ref: D4.3).
+mod desc {
+    pub const VALID: u64 = 1 << 0;
+    /// Set in table descriptors and level 3 page descriptors, clear in block
+    /// descriptors.
+    pub const TABLE: u64 = 1 << 1;
+    pub const PAGE: u64 = 1 << 1;
+    pub const ATTR_SHIFT: u64 = 2;
+    /// EL0 and EL1 may read and write. Data EL0 may write is never
+    /// executable at EL1.
+    pub const AP_RW: u64 = 0b01 << 6;
+    /// EL0 and EL1 may only read.
+    pub const AP_RO: u64 = 0b11 << 6;
+    pub const SH_INNER: u64 = 0b11 << 8;
+    pub const AF: u64 = 1 << 10;
+    pub const PXN: u64 = 1 << 53;
+    pub const UXN: u64 = 1 << 54;
+}
+
+/// Indices of the memory attributes in `MAIR_EL1`.
+#[repr(u64)]
+#[derive(Copy, Clone)]
+enum Attr {
+    /// Normal memory, write-back cacheable.
+    Normal = 0,
+    /// Device-nGnRnE memory.
+    Device = 1,
+    /// Normal memory, non-cacheable.
+    NonCacheable = 2,
+}
+
+/// The memory attributes `Attr` indexes.
+const MAIR: u64 = 0xFF | 0x00 << 8 | 0x44 << 16;
+
+/// `TCR_EL1`: a 32-bit address space translated by `TTBR0_EL1` with a 4 KiB
+/// granule and cacheable, inner shareable table walks. Walks through
+/// `TTBR1_EL1` are disabled.
+const TCR: u64 = 32 | 0b01 << 8 | 0b01 << 10 | 0b11 << 12 | 1 << 23;
+
+/// The `SCTLR_EL1` bits `enable()` sets: the MMU, the data and instruction
+/// caches, `UCI`, which lets EL0 maintain the caches, as processes run
+/// drivers that share memory with the GPU, and `UMA`, which lets EL0 mask
+/// interrupts to take an `IrqMutex`.
+const SCTLR_M: u64 = 1 << 0;
+const SCTLR_C: u64 = 1 << 2;
+const SCTLR_UMA: u64 = 1 << 9;
+const SCTLR_I: u64 = 1 << 12;
+const SCTLR_UCI: u64 = 1 << 26;
+
+extern "C" {
+    static _start: u8;
+    static __data_start: u8;
+}
+
+/// Returns a block or page descriptor mapping `address` with `attr`.
+fn descriptor(address: usize, attr: Attr, flags: u64) -> u64 {
+    address as u64 | (attr as u64) << desc::ATTR_SHIFT | desc::AF | desc::VALID | flags
+}
+
+/// Returns the descriptor of the block of the first GiB at `address`. The
+/// code, from `_start` to `__data_start`, isn't mapped by blocks.
+fn block(address: usize, ram_end: usize) -> u64 {
+    use self::desc::*;
+
+    if address >= IO_BASE {
+        descriptor(address, Attr::Device, AP_RW | PXN | UXN)
+    } else if address >= ram_end {
+        // The VideoCore's memory, which holds the framebuffer. The GPU
+        // doesn't see the ARM's caches.
+        descriptor(address, Attr::NonCacheable, AP_RW | PXN | UXN)
+    } else {
+        descriptor(address, Attr::Normal, AP_RW | SH_INNER | PXN | UXN)
+    }
+}
+
+/// Builds the identity mapping of the first 2 GiB and turns on the MMU and
+/// caches on this core. RAM is cacheable and the peripherals are device
+/// memory. The kernel's code is read-only and executable at EL1 and EL0,
+/// since processes run kernel code; everything else is never executable.
+///
+/// Must be called by core 0 before anything is locked, with `info`
+/// describing the RAM. Memory past the ARM's RAM, rounded down to a block,
+/// is non-cacheable, as the GPU uses it.
+///
+/// # Panics
+///
+/// Panics if the kernel's code doesn't fit in `CODE_TABLES` blocks.
+pub fn initialize(info: BootInfo) {
+    use self::desc::*;
+
+    // Without a memory map the allocator panics; everything is cacheable
+    // until then so locking, and so the panic message, still works.
+    let ram_end = info.memory_map()
+        .and_then(|map| map.regions().last().map(|region| region.end))
+        .unwrap_or(IO_BASE) & !(BLOCK_SIZE - 1);
+
+    let (code_start, code_end) = unsafe {
+        (&_start as *const u8 as usize, &__data_start as *const u8 as usize)
+    };
+    let first_code_block = code_start / BLOCK_SIZE;
+    let last_code_block = (code_end - 1) / BLOCK_SIZE;
+    assert!(last_code_block - first_code_block < CODE_TABLES, "vm: kernel code too large");
+
+    unsafe {
+        for (i, entry) in L2.0.iter_mut().enumerate() {
+            let address = i * BLOCK_SIZE;
+            *entry = match i {
+                i if i >= first_code_block && i <= last_code_block => {
+                    let table = &mut L3[i - first_code_block];
+                    for (j, page) in table.0.iter_mut().enumerate() {
+                        let address = address + j * PAGE_SIZE;
+                        let flags = match address >= code_start && address < code_end {
+                            true => AP_RO | SH_INNER,
+                            false => AP_RW | SH_INNER | PXN | UXN,
+                        };
+                        *page = descriptor(address, Attr::Normal, flags | PAGE);
+                    }
+
+                    &table.0 as *const u64 as u64 | TABLE | VALID
+                }
+                _ => block(address, ram_end),
+            };
+        }
+
+        // The ARM-local peripherals are in the second GiB.
+        L1.0[0] = &L2.0 as *const u64 as u64 | TABLE | VALID;
+        L1.0[1] = descriptor(1 << 30, Attr::Device, AP_RW | PXN | UXN);
+    }
+
+    enable();
+}
+
+/// Turns on the MMU and caches on this core with the tables `initialize()`
+/// built. Must be called by each secondary core before it locks anything.
+pub fn enable() {
+    let ttbr = unsafe { &L1.0 as *const u64 as u64 };
+
+    unsafe {
+        asm!("msr MAIR_EL1, $0
+              msr TCR_EL1, $1
+              msr TTBR0_EL1, $2
+              isb
+              tlbi vmalle1
+              ic iallu
+              dsb nsh
+              isb
+              mrs x0, SCTLR_EL1
+              orr x0, x0, $3
+              msr SCTLR_EL1, x0
+              isb"
+             :: "r"(MAIR), "r"(TCR), "r"(ttbr),
+                "r"(SCTLR_M | SCTLR_C | SCTLR_I | SCTLR_UCI | SCTLR_UMA)
+             : "x0", "memory" : "volatile");
+    }
+}
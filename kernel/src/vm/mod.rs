@@ -1,3 +1,5 @@
 mod address;
+mod mmu;
 
 pub use self::address::{PhysicalAddr, VirtualAddr};
+pub use self::mmu::{enable, initialize};
@@ -2,13 +2,19 @@ use core::{slice, str};
 
 use atags::raw;
 
-pub use atags::raw::{Core, Mem};
+pub use atags::raw::{Core, Mem, VideoText, Ramdisk, Initrd, Serial, Revision, VideoLfb};
 
 /// An ATAG.
 #[derive(Debug, Copy, Clone)]
 pub enum Atag {
     Core(raw::Core),
     Mem(raw::Mem),
+    VideoText(raw::VideoText),
+    Ramdisk(raw::Ramdisk),
+    Initrd(raw::Initrd),
+    Serial(raw::Serial),
+    Revision(raw::Revision),
+    VideoLfb(raw::VideoLfb),
     Cmd(&'static str),
     Unknown(u32),
     None
@@ -31,6 +37,54 @@ impl Atag {
         }
     }
 
+    /// Returns `Some` if this is a `VideoText` ATAG. Otherwise returns `None`.
+    pub fn video_text(self) -> Option<VideoText> {
+        match self {
+            Atag::VideoText(video_text) => Some(video_text),
+            _ => None
+        }
+    }
+
+    /// Returns `Some` if this is a `Ramdisk` ATAG. Otherwise returns `None`.
+    pub fn ramdisk(self) -> Option<Ramdisk> {
+        match self {
+            Atag::Ramdisk(ramdisk) => Some(ramdisk),
+            _ => None
+        }
+    }
+
+    /// Returns `Some` if this is an `Initrd` ATAG. Otherwise returns `None`.
+    pub fn initrd(self) -> Option<Initrd> {
+        match self {
+            Atag::Initrd(initrd) => Some(initrd),
+            _ => None
+        }
+    }
+
+    /// Returns `Some` if this is a `Serial` ATAG. Otherwise returns `None`.
+    pub fn serial(self) -> Option<Serial> {
+        match self {
+            Atag::Serial(serial) => Some(serial),
+            _ => None
+        }
+    }
+
+    /// Returns `Some` if this is a `Revision` ATAG. Otherwise returns `None`.
+    pub fn revision(self) -> Option<Revision> {
+        match self {
+            Atag::Revision(revision) => Some(revision),
+            _ => None
+        }
+    }
+
+    /// Returns `Some` if this is a `VideoLfb` ATAG. Otherwise returns `None`.
+    pub fn video_lfb(self) -> Option<VideoLfb> {
+        match self {
+            Atag::VideoLfb(video_lfb) => Some(video_lfb),
+            _ => None
+        }
+    }
+
     /// Returns `Some` with the command line string if this is a `Cmd` ATAG.
     /// Otherwise returns `None`.
     pub fn cmd(self) -> Option<&'static str> {
@@ -57,6 +111,42 @@ impl<'a> From<&'a raw::Mem> for Atag {
     }
 }
 
+impl<'a> From<&'a raw::VideoText> for Atag {
+    fn from(video_text: &raw::VideoText) -> Atag {
+        Atag::VideoText(*video_text)
+    }
+}
+
+impl<'a> From<&'a raw::Ramdisk> for Atag {
+    fn from(ramdisk: &raw::Ramdisk) -> Atag {
+        Atag::Ramdisk(*ramdisk)
+    }
+}
+
+impl<'a> From<&'a raw::Initrd> for Atag {
+    fn from(initrd: &raw::Initrd) -> Atag {
+        Atag::Initrd(*initrd)
+    }
+}
+
+impl<'a> From<&'a raw::Serial> for Atag {
+    fn from(serial: &raw::Serial) -> Atag {
+        Atag::Serial(*serial)
+    }
+}
+
+impl<'a> From<&'a raw::Revision> for Atag {
+    fn from(revision: &raw::Revision) -> Atag {
+        Atag::Revision(*revision)
+    }
+}
+
+impl<'a> From<&'a raw::VideoLfb> for Atag {
+    fn from(video_lfb: &raw::VideoLfb) -> Atag {
+        Atag::VideoLfb(*video_lfb)
+    }
+}
+
 impl<'a> From<&'a raw::Cmd> for Atag {
     fn from(cmd: &raw::Cmd) -> Atag {
         unsafe {
@@ -80,6 +170,12 @@ impl<'a> From<&'a raw::Atag> for Atag {
             match (atag.tag, &atag.kind) {
                 (raw::Atag::CORE, &raw::Kind { core }) => Atag::from(&core),
                 (raw::Atag::MEM, &raw::Kind { mem }) => Atag::from(&mem),
+                (raw::Atag::VIDEOTEXT, &raw::Kind { video_text }) => Atag::from(&video_text),
+                (raw::Atag::RAMDISK, &raw::Kind { ramdisk }) => Atag::from(&ramdisk),
+                (raw::Atag::INITRD2, &raw::Kind { initrd }) => Atag::from(&initrd),
+                (raw::Atag::SERIAL, &raw::Kind { serial }) => Atag::from(&serial),
+                (raw::Atag::REVISION, &raw::Kind { revision }) => Atag::from(&revision),
+                (raw::Atag::VIDEOLFB, &raw::Kind { video_lfb }) => Atag::from(&video_lfb),
                 (raw::Atag::CMDLINE, &raw::Kind { ref cmd }) => Atag::from(cmd),
                 (raw::Atag::NONE, _) => Atag::None,
                 (id, _) => Atag::Unknown(id)
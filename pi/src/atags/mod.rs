@@ -18,6 +18,18 @@ impl Atags {
             ptr: unsafe { &*(ATAG_BASE as *const raw::Atag) }
         }
     }
+
+    /// Returns the address one past the end of the remaining ATAGS, including
+    /// the terminating `NONE` tag.
+    pub fn end(&self) -> usize {
+        let mut atag = self.ptr;
+        while let Some(next) = atag.next() {
+            atag = next;
+        }
+
+        let dwords = ::core::cmp::max(atag.dwords, 2) as usize;
+        atag as *const raw::Atag as usize + dwords * 4
+    }
 }
 
 impl Iterator for Atags {
@@ -38,6 +38,12 @@ impl Atag {
 pub union Kind {
     pub core: Core,
     pub mem: Mem,
+    pub video_text: VideoText,
+    pub ramdisk: Ramdisk,
+    pub initrd: Initrd,
+    pub serial: Serial,
+    pub revision: Revision,
+    pub video_lfb: VideoLfb,
     pub cmd: Cmd
 }
 
@@ -58,6 +64,73 @@ pub struct Mem {
     pub start: u32
 }
 
+/// A `VIDEOTEXT` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VideoText {
+    pub x: u8,
+    pub y: u8,
+    pub video_page: u16,
+    pub video_mode: u8,
+    pub video_cols: u8,
+    pub video_ega_bx: u16,
+    pub video_lines: u8,
+    pub video_isvga: u8,
+    pub video_points: u16
+}
+
+/// A `RAMDISK` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Ramdisk {
+    pub flags: u32,
+    pub size: u32,
+    pub start: u32
+}
+
+/// An `INITRD2` ATAG. `start` is a physical address.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Initrd {
+    pub start: u32,
+    pub size: u32
+}
+
+/// A `SERIAL` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Serial {
+    pub low: u32,
+    pub high: u32
+}
+
+/// A `REVISION` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Revision {
+    pub rev: u32
+}
+
+/// A `VIDEOLFB` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VideoLfb {
+    pub width: u16,
+    pub height: u16,
+    pub depth: u16,
+    pub line_length: u16,
+    pub base: u32,
+    pub size: u32,
+    pub red_size: u8,
+    pub red_pos: u8,
+    pub green_size: u8,
+    pub green_pos: u8,
+    pub blue_size: u8,
+    pub blue_pos: u8,
+    pub rsvd_size: u8,
+    pub rsvd_pos: u8
+}
+
 /// A `CMDLINE` ATAG.
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
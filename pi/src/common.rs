@@ -1,6 +1,51 @@
 /// The address where I/O peripherals are mapped to.
 pub const IO_BASE: usize = 0x3F000000;
 
+/// The address one past the end of the I/O peripheral window, including the
+/// ARM-local peripherals at `0x40000000`.
+pub const IO_END: usize = 0x40040000;
+
+/// The default frequency of the core clock, which drives the mini UART, SPI
+/// and I2C controllers, used if the firmware can't be asked for the actual
+/// one.
+pub const CORE_CLOCK_HZ: u32 = 250 * 1000 * 1000;
+
+/// The size of a line of the ARM's data caches.
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Writes the data cache lines holding the `len` bytes at `address` back to
+/// memory and invalidates them. The GPU and the DMA engine don't see the
+/// ARM's caches: memory they read must be cleaned first, and memory they
+/// wrote must be invalidated before the ARM reads it.
+pub fn clean_invalidate_dcache(address: usize, len: usize) {
+    let mut line = address & !(CACHE_LINE_SIZE - 1);
+    while line < address + len {
+        unsafe { asm!("dc civac, $0" :: "r"(line) : "memory" : "volatile") };
+        line += CACHE_LINE_SIZE;
+    }
+
+    unsafe { asm!("dsb sy" ::: "memory" : "volatile") };
+}
+
+/// Returns the clock divider for the fastest clock no faster than `hz` that
+/// can be derived from a clock of `clock` Hz, for the SPI and I2C
+/// controllers. The divider is even, as the hardware ignores its lowest bit,
+/// and at least 2. Returns `None` if `hz` is 0 or the divider would exceed
+/// `max`.
+pub fn divider(clock: u32, hz: u32, max: u32) -> Option<u32> {
+    if hz == 0 {
+        return None;
+    }
+
+    // Round up, to an even divider of at least 2.
+    let divider = clock / hz + if clock % hz != 0 { 1 } else { 0 };
+    let divider = ((divider + 1) & !1).max(2);
+    match divider <= max {
+        true => Some(divider),
+        false => None,
+    }
+}
+
 /// Generates `pub enums` with no variants for each `ident` passed in.
 pub macro states($($name:ident),*) {
     $(
@@ -0,0 +1,448 @@
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile};
+
+use common::{clean_invalidate_dcache, IO_BASE, IO_END};
+use interrupt::Interrupt;
+
+/// The base address for the DMA registers of channel 0. Channel `n`'s are at
+/// `n * CHANNEL_STRIDE` bytes from it.
+const DMA_REG_BASE: usize = IO_BASE + 0x7000;
+
+/// The distance between two channels' registers.
+const CHANNEL_STRIDE: usize = 0x100;
+
+/// The `INT_STATUS` register: a bit per channel with a pending interrupt.
+const DMA_INT_STATUS: *const Volatile<u32> = (DMA_REG_BASE + 0xFE0) as *const Volatile<u32>;
+
+/// The `ENABLE` register: a bit per enabled channel.
+const DMA_ENABLE: *mut Volatile<u32> = (DMA_REG_BASE + 0xFF0) as *mut Volatile<u32>;
+
+/// The channels the firmware leaves to the ARM, as in the device tree's
+/// default `brcm,dma-channel-mask`. Channel 15 is elsewhere and unused.
+const CHANNEL_MASK: usize = 0x7F35;
+
+/// The first "lite" channel. Lite channels have a smaller FIFO and can't
+/// transfer more than `LITE_MAX_LEN` bytes per control block.
+const FIRST_LITE_CHANNEL: usize = 7;
+
+/// The most bytes a lite channel transfers per control block.
+const LITE_MAX_LEN: usize = 0xFFFF;
+
+/// The bus address of the I/O peripherals.
+const IO_BUS_BASE: u32 = 0x7E00_0000;
+
+/// The bus alias that makes the DMA engine bypass the VideoCore's L2 cache.
+const UNCACHED_BUS_ALIAS: u32 = 0xC000_0000;
+
+/// Bit fields of a channel's `CS` (control and status) register.
+#[repr(u32)]
+enum Control {
+    Active = 1 << 0,
+    End = 1 << 1,
+    Interrupt = 1 << 2,
+    Error = 1 << 8,
+    WaitForWrites = 1 << 28,
+    Abort = 1 << 30,
+    Reset = 1 << 31,
+}
+
+/// The offset of the `PRIORITY` field in the `CS` register. `PANIC_PRIORITY`
+/// follows.
+const CS_PRIORITY_SHIFT: u32 = 16;
+
+/// The priority transfers are started with, and the one used while the
+/// peripheral signals a panic.
+const PRIORITY: u32 = 8;
+const PANIC_PRIORITY: u32 = 15;
+
+/// Bit fields of the `TI` (transfer information) word of a control block.
+#[repr(u32)]
+enum TransferInfo {
+    InterruptEnable = 1 << 0,
+    WaitForResponse = 1 << 3,
+    DestIncrement = 1 << 4,
+    DestDreq = 1 << 6,
+    SourceIncrement = 1 << 8,
+    SourceDreq = 1 << 10,
+}
+
+/// The offsets of the `BURST_LENGTH` and `PERMAP` fields of `TI`.
+const TI_BURST_SHIFT: u32 = 12;
+const TI_PERMAP_SHIFT: u32 = 16;
+
+/// The burst length used for memory to memory copies.
+const COPY_BURST: u32 = 4;
+
+/// Bit fields of a channel's `DEBUG` register.
+#[repr(u32)]
+enum Debug {
+    ReadLastNotSet = 1 << 0,
+    Fifo = 1 << 1,
+    Read = 1 << 2,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CS: Volatile<u32>,
+    CONBLK_AD: Volatile<u32>,
+    TI: ReadVolatile<u32>,
+    SOURCE_AD: ReadVolatile<u32>,
+    DEST_AD: ReadVolatile<u32>,
+    TXFR_LEN: ReadVolatile<u32>,
+    STRIDE: ReadVolatile<u32>,
+    NEXTCONBK: Volatile<u32>,
+    DEBUG: Volatile<u32>,
+}
+
+/// A peripheral that paces a transfer with its data requests (DREQ), so
+/// data only moves when the peripheral's FIFO can take or provide it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Dreq {
+    Pwm = 5,
+    SpiTx = 6,
+    SpiRx = 7,
+    Emmc = 11,
+    UartTx = 12,
+    SdHost = 13,
+    UartRx = 14,
+}
+
+/// Error type for failed DMA transfers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// An AXI read didn't signal its last word.
+    ReadLastNotSet,
+    /// The peripheral FIFO was read or written out of turn.
+    Fifo,
+    /// The channel's read from memory or a peripheral failed.
+    Read,
+}
+
+/// Returns the address the DMA engine sees the ARM physical address
+/// `address` at. Memory is accessed bypassing the VideoCore's L2 cache.
+pub fn bus_address(address: usize) -> u32 {
+    if address >= IO_BASE && address < IO_END {
+        (address - IO_BASE) as u32 + IO_BUS_BASE
+    } else {
+        address as u32 | UNCACHED_BUS_ALIAS
+    }
+}
+
+/// A DMA control block: the description of a single transfer, and of the
+/// control block that follows it, if any.
+///
+/// The DMA engine reads the control block and the memory it refers to
+/// directly, while the CPU may continue. Control blocks and their buffers
+/// must therefore stay in place and unchanged until the transfer completes.
+#[repr(C, align(32))]
+#[derive(Debug, Clone)]
+pub struct ControlBlock {
+    ti: u32,
+    source: u32,
+    dest: u32,
+    len: u32,
+    stride: u32,
+    next: u32,
+    _reserved: [u32; 2],
+}
+
+impl ControlBlock {
+    /// Returns a control block with the transfer information `ti`.
+    fn new(ti: u32, source: u32, dest: u32, len: usize) -> ControlBlock {
+        ControlBlock {
+            ti: ti | TransferInfo::WaitForResponse as u32,
+            source,
+            dest,
+            len: len as u32,
+            stride: 0,
+            next: 0,
+            _reserved: [0; 2],
+        }
+    }
+
+    /// Returns a control block that copies `len` bytes from the memory at
+    /// `source` to the memory at `dest`.
+    pub fn copy(source: usize, dest: usize, len: usize) -> ControlBlock {
+        let ti = TransferInfo::SourceIncrement as u32
+            | TransferInfo::DestIncrement as u32
+            | COPY_BURST << TI_BURST_SHIFT;
+        ControlBlock::new(ti, bus_address(source), bus_address(dest), len)
+    }
+
+    /// Returns a control block that writes `len` bytes from the memory at
+    /// `source` to the peripheral register at the ARM physical address
+    /// `register`, paced by `dreq`.
+    pub fn to_peripheral(source: usize, register: usize, len: usize, dreq: Dreq) -> ControlBlock {
+        let ti = TransferInfo::SourceIncrement as u32
+            | TransferInfo::DestDreq as u32
+            | (dreq as u32) << TI_PERMAP_SHIFT;
+        ControlBlock::new(ti, bus_address(source), bus_address(register), len)
+    }
+
+    /// Returns a control block that reads `len` bytes from the peripheral
+    /// register at the ARM physical address `register` to the memory at
+    /// `dest`, paced by `dreq`.
+    pub fn from_peripheral(register: usize, dest: usize, len: usize, dreq: Dreq) -> ControlBlock {
+        let ti = TransferInfo::DestIncrement as u32
+            | TransferInfo::SourceDreq as u32
+            | (dreq as u32) << TI_PERMAP_SHIFT;
+        ControlBlock::new(ti, bus_address(register), bus_address(dest), len)
+    }
+
+    /// Makes the channel raise its interrupt once this control block's
+    /// transfer is complete.
+    pub fn set_interrupt(&mut self, enable: bool) {
+        if enable {
+            self.ti |= TransferInfo::InterruptEnable as u32;
+        } else {
+            self.ti &= !(TransferInfo::InterruptEnable as u32);
+        }
+    }
+
+    /// Makes `next` the control block the channel continues with after this
+    /// one, or ends the chain here if `next` is `None`.
+    pub fn set_next(&mut self, next: Option<&ControlBlock>) {
+        self.next = match next {
+            Some(next) => bus_address(next as *const ControlBlock as usize),
+            None => 0,
+        };
+    }
+}
+
+/// The channels allocated with `Channel::allocate()`, as a bit set.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// An allocated DMA channel. The channel is reset and freed when dropped.
+pub struct Channel {
+    index: usize,
+    registers: &'static mut Registers,
+}
+
+impl Channel {
+    /// Allocates the lowest free channel the ARM may use, resets it and
+    /// enables it. Returns `None` if all channels are in use.
+    pub fn allocate() -> Option<Channel> {
+        let mut allocated = ALLOCATED.load(Ordering::Relaxed);
+        let index = loop {
+            let free = CHANNEL_MASK & !allocated;
+            if free == 0 {
+                return None;
+            }
+
+            let index = free.trailing_zeros() as usize;
+            let taken = allocated | 1 << index;
+            match ALLOCATED.compare_exchange(
+                allocated,
+                taken,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break index,
+                Err(actual) => allocated = actual,
+            }
+        };
+
+        let address = DMA_REG_BASE + index * CHANNEL_STRIDE;
+        let mut channel = Channel {
+            index,
+            registers: unsafe { &mut *(address as *mut Registers) },
+        };
+
+        unsafe { (*DMA_ENABLE).or_mask(1 << index) };
+        channel.reset();
+        Some(channel)
+    }
+
+    /// The number of this channel.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns `true` if this is a lite channel, which can transfer at most
+    /// 65535 bytes per control block.
+    pub fn is_lite(&self) -> bool {
+        self.index >= FIRST_LITE_CHANNEL
+    }
+
+    /// Returns the interrupt this channel raises.
+    pub fn interrupt(&self) -> Interrupt {
+        match self.index {
+            0 => Interrupt::Dma0,
+            1 => Interrupt::Dma1,
+            2 => Interrupt::Dma2,
+            3 => Interrupt::Dma3,
+            4 => Interrupt::Dma4,
+            5 => Interrupt::Dma5,
+            6 => Interrupt::Dma6,
+            7 => Interrupt::Dma7,
+            8 => Interrupt::Dma8,
+            9 => Interrupt::Dma9,
+            10 => Interrupt::Dma10,
+            _ => Interrupt::DmaShared,
+        }
+    }
+
+    /// Stops any transfer and resets the channel.
+    pub fn reset(&mut self) {
+        self.registers.CS.write(Control::Reset as u32);
+        self.registers.CS.write(Control::End as u32 | Control::Interrupt as u32);
+        self.registers.DEBUG.write(
+            Debug::ReadLastNotSet as u32 | Debug::Fifo as u32 | Debug::Read as u32,
+        );
+    }
+
+    /// Starts the chain of transfers beginning with `block`. Returns
+    /// immediately.
+    ///
+    /// # Safety
+    ///
+    /// The DMA engine accesses whatever addresses the control blocks hold.
+    /// The memory and registers `block` and the control blocks chained to it
+    /// refer to must be valid to read or write as the transfers do. They,
+    /// and the control blocks themselves, must stay in place and unchanged
+    /// until the transfer completes, as reported by `is_active()`, `wait()`
+    /// or the channel's interrupt. The DMA engine doesn't see the ARM's
+    /// caches: the control blocks and the memory they read must have been
+    /// written back with `common::clean_invalidate_dcache()`, and the memory
+    /// they write must be invalidated with it before the transfer and again
+    /// before it's read.
+    pub unsafe fn start(&mut self, block: &ControlBlock) {
+        self.registers.CS.write(Control::End as u32 | Control::Interrupt as u32);
+        self.registers.CONBLK_AD.write(bus_address(block as *const ControlBlock as usize));
+        self.registers.CS.write(
+            Control::Active as u32
+                | Control::WaitForWrites as u32
+                | PRIORITY << CS_PRIORITY_SHIFT
+                | PANIC_PRIORITY << (CS_PRIORITY_SHIFT + 4),
+        );
+    }
+
+    /// Returns `true` while a transfer is in progress.
+    pub fn is_active(&self) -> bool {
+        self.registers.CS.has_mask(Control::Active as u32)
+    }
+
+    /// Blocks until the current chain of transfers is complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that stopped the transfer, if any. The channel is
+    /// then reset.
+    pub fn wait(&mut self) -> Result<(), Error> {
+        while self.is_active() {
+            if self.registers.CS.has_mask(Control::Error as u32) {
+                break;
+            }
+        }
+
+        self.check()
+    }
+
+    /// Returns the error flagged on this channel, if any, resetting it.
+    fn check(&mut self) -> Result<(), Error> {
+        if !self.registers.CS.has_mask(Control::Error as u32) {
+            return Ok(());
+        }
+
+        let debug = self.registers.DEBUG.read();
+        let error = if debug & Debug::Read as u32 != 0 {
+            Error::Read
+        } else if debug & Debug::Fifo as u32 != 0 {
+            Error::Fifo
+        } else {
+            Error::ReadLastNotSet
+        };
+
+        self.reset();
+        Err(error)
+    }
+
+    /// Runs the chain of transfers beginning with `block` and blocks until
+    /// it is complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that stopped the transfer, if any.
+    ///
+    /// # Safety
+    ///
+    /// The DMA engine accesses whatever addresses the control blocks hold.
+    /// The memory and registers `block` and the control blocks chained to it
+    /// refer to must be valid to read or write as the transfers do, and must
+    /// stay in place and unchanged until this returns. Caches must be
+    /// maintained around the transfer as `start()` describes.
+    pub unsafe fn transfer(&mut self, block: &ControlBlock) -> Result<(), Error> {
+        self.start(block);
+        self.wait()
+    }
+
+    /// Copies `source` to `dest`, which must be the same length, and blocks
+    /// until the copy is complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that stopped the copy, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` and `dest` differ in length.
+    pub fn copy(&mut self, dest: &mut [u8], source: &[u8]) -> Result<(), Error> {
+        assert_eq!(dest.len(), source.len(), "dma: copy between slices of different lengths");
+
+        let max_len = if self.is_lite() { LITE_MAX_LEN } else { 1 << 30 };
+        let mut offset = 0;
+        while offset < source.len() {
+            let len = max_len.min(source.len() - offset);
+            let from = source[offset..].as_ptr() as usize;
+            let to = dest[offset..].as_mut_ptr() as usize;
+            let block = ControlBlock::copy(from, to, len);
+
+            let address = &block as *const ControlBlock as usize;
+            clean_invalidate_dcache(address, mem::size_of::<ControlBlock>());
+            clean_invalidate_dcache(from, len);
+            clean_invalidate_dcache(to, len);
+            // The block refers to the slices, which are borrowed for the
+            // whole copy.
+            let result = unsafe { self.transfer(&block) };
+            clean_invalidate_dcache(to, len);
+            result?;
+
+            offset += len;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the current transfer after its outstanding writes.
+    pub fn abort(&mut self) {
+        self.registers.CS.or_mask(Control::Abort as u32);
+        while self.is_active() {}
+    }
+
+    /// Clears this channel's interrupt. Returns `true` if it was pending.
+    pub fn clear_interrupt(&mut self) -> bool {
+        let pending = self.registers.CS.has_mask(Control::Interrupt as u32);
+        if pending {
+            self.registers.CS.or_mask(Control::Interrupt as u32);
+        }
+
+        pending
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.reset();
+        ALLOCATED.fetch_and(!(1 << self.index), Ordering::Release);
+    }
+}
+
+/// Returns a bit set of the channels with a pending interrupt: bit `n` is
+/// set if channel `n` raised its interrupt.
+pub fn pending_interrupts() -> u32 {
+    unsafe { (*DMA_INT_STATUS).read() }
+}
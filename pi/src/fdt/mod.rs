@@ -0,0 +1,272 @@
+mod raw;
+mod node;
+
+#[cfg(test)]
+mod tests;
+
+use core::{fmt, slice};
+
+pub use self::node::{Node, Properties, Property, Reg};
+
+/// The deepest node nesting `Nodes` tracks `#address-cells` for.
+const MAX_DEPTH: usize = 16;
+
+/// A flattened device tree (FDT) blob.
+#[derive(Copy, Clone)]
+pub struct Fdt {
+    data: &'static [u8],
+    structs: usize,
+    strings: usize,
+    rsvmap: usize,
+}
+
+/// A token in the structure block of a device tree.
+#[derive(Copy, Clone)]
+enum Token {
+    BeginNode(&'static str),
+    EndNode,
+    Prop(&'static str, &'static [u8]),
+    End,
+}
+
+impl Fdt {
+    /// Returns the device tree at address `addr` if there is a valid FDT
+    /// header there. Otherwise returns `None`.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be readable memory. If it contains an FDT header, the
+    /// entire blob must remain valid and unmodified for the rest of the
+    /// program.
+    pub unsafe fn from_addr(addr: usize) -> Option<Fdt> {
+        if addr == 0 || addr % 8 != 0 {
+            return None;
+        }
+
+        let header = &*(addr as *const raw::Header);
+        if u32::from_be(header.magic) != raw::Header::MAGIC {
+            return None;
+        }
+
+        let size = u32::from_be(header.totalsize) as usize;
+        Fdt::from_bytes(slice::from_raw_parts(addr as *const u8, size))
+    }
+
+    /// Parses the device tree in `data`. Returns `None` if `data` does not
+    /// start with a valid FDT header of a supported version.
+    pub fn from_bytes(data: &'static [u8]) -> Option<Fdt> {
+        let field = |n: usize| raw::be32(data, n * 4).map(|v| v as usize);
+
+        let (magic, totalsize) = (field(0)?, field(1)?);
+        let (structs, strings, rsvmap) = (field(2)?, field(3)?, field(4)?);
+        let last_comp_version = field(6)? as u32;
+
+        if magic as u32 != raw::Header::MAGIC
+            || last_comp_version > raw::Header::VERSION
+            || totalsize > data.len()
+            || structs >= totalsize
+            || strings >= totalsize
+            || rsvmap >= totalsize
+        {
+            return None;
+        }
+
+        Some(Fdt {
+            data: &data[..totalsize],
+            structs,
+            strings,
+            rsvmap,
+        })
+    }
+
+    /// Returns the address this device tree is located at.
+    pub fn addr(&self) -> usize {
+        self.data.as_ptr() as usize
+    }
+
+    /// Returns the total size of this device tree blob in bytes.
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns an iterator over every node in the tree in depth-first order,
+    /// starting with the root node.
+    pub fn nodes(&self) -> Nodes {
+        Nodes {
+            fdt: *self,
+            offset: self.structs,
+            depth: 0,
+            cells: [(0, 0); MAX_DEPTH],
+        }
+    }
+
+    /// Returns the node at `path`, if there is one. `path` is absolute, such
+    /// as `/chosen` or `/soc/gpio@7e200000`. Components without a unit
+    /// address match any unit address, so `/memory` finds `/memory@0`.
+    pub fn find(&self, path: &str) -> Option<Node> {
+        let components = || path.split('/').filter(|c| !c.is_empty());
+        let len = components().count();
+
+        // The number of components of `path` the current node's ancestors
+        // (and maybe the node itself) match.
+        let mut matched = 0;
+        for node in self.nodes() {
+            if node.depth() == 0 {
+                if len == 0 {
+                    return Some(node);
+                }
+                continue;
+            }
+
+            if node.depth() <= matched {
+                matched = node.depth() - 1;
+            }
+
+            if node.depth() == matched + 1 {
+                if components().nth(matched).map_or(false, |c| node.matches(c)) {
+                    matched += 1;
+                    if matched == len {
+                        return Some(node);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns an iterator over the `(address, size)` regions of RAM listed
+    /// in the `/memory` node.
+    pub fn memory(&self) -> Option<Reg> {
+        self.find("/memory")?.reg()
+    }
+
+    /// Returns the kernel command line from `/chosen/bootargs`.
+    pub fn bootargs(&self) -> Option<&'static str> {
+        self.find("/chosen")?.property("bootargs")?.as_str()
+    }
+
+    /// Returns the `(address, size)` of the peripheral window as seen by the
+    /// ARM, read from the first entry of the `/soc` node's `ranges`.
+    pub fn peripherals(&self) -> Option<(usize, usize)> {
+        let (_, parent, size) = self.find("/soc")?.first_range()?;
+        Some((parent as usize, size as usize))
+    }
+
+    /// Returns an iterator over the `(address, size)` entries of the memory
+    /// reservation block.
+    pub fn reservations(&self) -> Reservations {
+        Reservations {
+            data: self.data,
+            offset: self.rsvmap,
+        }
+    }
+
+    /// Reads the token at `offset` in the structure block, skipping `NOP`s.
+    /// Returns the token and the offset of the token following it.
+    fn token(&self, offset: usize) -> Option<(Token, usize)> {
+        let mut offset = offset;
+        loop {
+            let token = raw::be32(self.data, offset)?;
+            offset += 4;
+
+            match token {
+                raw::NOP => continue,
+                raw::BEGIN_NODE => {
+                    let name = raw::c_str(self.data, offset)?;
+                    let next = raw::align4(offset + name.len() + 1);
+                    return Some((Token::BeginNode(name), next));
+                }
+                raw::END_NODE => return Some((Token::EndNode, offset)),
+                raw::PROP => {
+                    let len = raw::be32(self.data, offset)? as usize;
+                    let name_offset = raw::be32(self.data, offset + 4)? as usize;
+                    let name = raw::c_str(self.data, self.strings + name_offset)?;
+                    let value = self.data.get(offset + 8..offset + 8 + len)?;
+                    let next = raw::align4(offset + 8 + len);
+                    return Some((Token::Prop(name, value), next));
+                }
+                raw::END => return Some((Token::End, offset)),
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Fdt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fdt")
+            .field("address", &self.data.as_ptr())
+            .field("size", &self.data.len())
+            .finish()
+    }
+}
+
+/// A depth-first iterator over the nodes of a device tree.
+pub struct Nodes {
+    fdt: Fdt,
+    offset: usize,
+    depth: usize,
+    /// The `(#address-cells, #size-cells)` of each node on the current path.
+    cells: [(u32, u32); MAX_DEPTH],
+}
+
+impl Iterator for Nodes {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        loop {
+            let (token, next) = self.fdt.token(self.offset)?;
+            self.offset = next;
+
+            match token {
+                Token::BeginNode(name) => {
+                    let (address_cells, size_cells) = match self.depth {
+                        0 => (2, 1),
+                        d => self.cells[::core::cmp::min(d, MAX_DEPTH) - 1],
+                    };
+
+                    let node = Node {
+                        fdt: self.fdt,
+                        name,
+                        props: next,
+                        depth: self.depth,
+                        address_cells,
+                        size_cells,
+                    };
+
+                    if self.depth < MAX_DEPTH {
+                        self.cells[self.depth] = node.child_cells();
+                    }
+
+                    self.depth += 1;
+                    return Some(node);
+                }
+                Token::EndNode => self.depth = self.depth.saturating_sub(1),
+                Token::Prop(..) => continue,
+                Token::End => return None,
+            }
+        }
+    }
+}
+
+/// An iterator over the entries of a device tree's memory reservation block.
+pub struct Reservations {
+    data: &'static [u8],
+    offset: usize,
+}
+
+impl Iterator for Reservations {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        let address = raw::be64(self.data, self.offset)?;
+        let size = raw::be64(self.data, self.offset + 8)?;
+        if address == 0 && size == 0 {
+            return None;
+        }
+
+        self.offset += 16;
+        Some((address, size))
+    }
+}
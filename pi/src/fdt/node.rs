@@ -0,0 +1,204 @@
+use core::{fmt, str};
+
+use fdt::raw;
+use fdt::{Fdt, Token};
+
+/// The `#address-cells` a node's children use when the node doesn't say.
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+
+/// The `#size-cells` a node's children use when the node doesn't say.
+const DEFAULT_SIZE_CELLS: u32 = 1;
+
+/// A node in a device tree.
+#[derive(Copy, Clone)]
+pub struct Node {
+    pub(super) fdt: Fdt,
+    pub(super) name: &'static str,
+    /// Offset of the first token after this node's `BEGIN_NODE` token.
+    pub(super) props: usize,
+    pub(super) depth: usize,
+    /// The `#address-cells` of this node's parent.
+    pub(super) address_cells: u32,
+    /// The `#size-cells` of this node's parent.
+    pub(super) size_cells: u32,
+}
+
+impl Node {
+    /// Returns the name of this node, including the unit address, if any.
+    /// The root node's name is empty.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the depth of this node in the tree. The root node is at depth
+    /// `0`.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns `true` if `component` names this node. A component without a
+    /// unit address matches any unit address: `memory` matches `memory@0`.
+    pub fn matches(&self, component: &str) -> bool {
+        if self.name == component {
+            return true;
+        }
+
+        !component.contains('@')
+            && self.name.starts_with(component)
+            && self.name[component.len()..].starts_with('@')
+    }
+
+    /// Returns an iterator over the properties of this node.
+    pub fn properties(&self) -> Properties {
+        Properties { fdt: self.fdt, offset: self.props }
+    }
+
+    /// Returns the property named `name`, if there is one.
+    pub fn property(&self, name: &str) -> Option<Property> {
+        self.properties().find(|prop| prop.name == name)
+    }
+
+    /// Returns the `(#address-cells, #size-cells)` this node's children use.
+    pub fn child_cells(&self) -> (u32, u32) {
+        let cells = |name, default| {
+            self.property(name)
+                .and_then(|prop| prop.as_u32())
+                .unwrap_or(default)
+        };
+
+        (
+            cells("#address-cells", DEFAULT_ADDRESS_CELLS),
+            cells("#size-cells", DEFAULT_SIZE_CELLS),
+        )
+    }
+
+    /// Returns an iterator over the `(address, size)` pairs in this node's
+    /// `reg` property, if it has one.
+    pub fn reg(&self) -> Option<Reg> {
+        Some(Reg {
+            value: self.property("reg")?.value,
+            address_cells: self.address_cells,
+            size_cells: self.size_cells,
+        })
+    }
+
+    /// Returns the first `(child address, parent address, size)` triple of
+    /// this node's `ranges` property, if it has one.
+    pub fn first_range(&self) -> Option<(u64, u64, u64)> {
+        let value = self.property("ranges")?.value;
+        let (child_cells, size_cells) = self.child_cells();
+
+        let (child, rest) = read_cells(value, child_cells)?;
+        let (parent, rest) = read_cells(rest, self.address_cells)?;
+        let (size, _) = read_cells(rest, size_cells)?;
+        Some((child, parent, size))
+    }
+}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("name", &self.name)
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
+
+/// A property of a device tree node.
+#[derive(Debug, Copy, Clone)]
+pub struct Property {
+    pub name: &'static str,
+    pub value: &'static [u8],
+}
+
+impl Property {
+    /// Returns the value of this property as a string if it is a valid,
+    /// NUL-terminated UTF-8 string.
+    pub fn as_str(&self) -> Option<&'static str> {
+        let (&last, bytes) = self.value.split_last()?;
+        if last != 0 {
+            return None;
+        }
+
+        str::from_utf8(bytes).ok()
+    }
+
+    /// Returns the value of this property as a `u32` if it is exactly one
+    /// cell long.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.value.len() {
+            4 => raw::be32(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of this property as a `u64` if it is one or two
+    /// cells long.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => raw::be32(self.value, 0).map(|v| v as u64),
+            8 => raw::be64(self.value, 0),
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the properties of a device tree node.
+pub struct Properties {
+    fdt: Fdt,
+    offset: usize,
+}
+
+impl Iterator for Properties {
+    type Item = Property;
+
+    fn next(&mut self) -> Option<Property> {
+        match self.fdt.token(self.offset)? {
+            (Token::Prop(name, value), next) => {
+                self.offset = next;
+                Some(Property { name, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the `(address, size)` pairs of a `reg` property.
+pub struct Reg {
+    value: &'static [u8],
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl Iterator for Reg {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        if self.value.is_empty() {
+            return None;
+        }
+
+        let (address, rest) = read_cells(self.value, self.address_cells)?;
+        let (size, rest) = read_cells(rest, self.size_cells)?;
+        self.value = rest;
+        Some((address, size))
+    }
+}
+
+/// Reads a `cells`-cell big-endian number from the front of `bytes`.
+/// Returns the number and the remaining bytes. Numbers wider than two cells
+/// are not supported.
+fn read_cells(bytes: &'static [u8], cells: u32) -> Option<(u64, &'static [u8])> {
+    let len = cells as usize * 4;
+    if cells > 2 || bytes.len() < len {
+        return None;
+    }
+
+    let value = match cells {
+        0 => 0,
+        1 => raw::be32(bytes, 0)? as u64,
+        _ => raw::be64(bytes, 0)?,
+    };
+
+    Some((value, &bytes[len..]))
+}
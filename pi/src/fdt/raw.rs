@@ -0,0 +1,54 @@
+/// The header of a flattened device tree as laid out in memory. All fields
+/// are stored big-endian.
+#[repr(C)]
+pub struct Header {
+    pub magic: u32,
+    pub totalsize: u32,
+    pub off_dt_struct: u32,
+    pub off_dt_strings: u32,
+    pub off_mem_rsvmap: u32,
+    pub version: u32,
+    pub last_comp_version: u32,
+    pub boot_cpuid_phys: u32,
+    pub size_dt_strings: u32,
+    pub size_dt_struct: u32,
+}
+
+impl Header {
+    pub const MAGIC: u32 = 0xd00dfeed;
+
+    /// The newest FDT version this parser is compatible with.
+    pub const VERSION: u32 = 17;
+}
+
+/// The tokens of the structure block.
+pub const BEGIN_NODE: u32 = 0x1;
+pub const END_NODE: u32 = 0x2;
+pub const PROP: u32 = 0x3;
+pub const NOP: u32 = 0x4;
+pub const END: u32 = 0x9;
+
+/// Reads the big-endian `u32` at `offset` in `bytes`, if it is in bounds.
+pub fn be32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset.checked_add(4)?)?;
+    Some((b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32)
+}
+
+/// Reads the big-endian `u64` at `offset` in `bytes`, if it is in bounds.
+pub fn be64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let hi = be32(bytes, offset)? as u64;
+    let lo = be32(bytes, offset.checked_add(4)?)? as u64;
+    Some(hi << 32 | lo)
+}
+
+/// Reads the NUL-terminated string starting at `offset` in `bytes`.
+pub fn c_str(bytes: &'static [u8], offset: usize) -> Option<&'static str> {
+    let rest = bytes.get(offset..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    ::core::str::from_utf8(&rest[..len]).ok()
+}
+
+/// Rounds `offset` up to the next 4-byte boundary.
+pub fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
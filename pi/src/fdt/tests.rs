@@ -0,0 +1,187 @@
+use fdt::Fdt;
+
+/// A device tree with a nested node, a memory reservation and a `NOP`:
+///
+/// ```text
+/// /memreserve/ 0x1000 0x2000;
+/// / {
+///     #address-cells = <1>;
+///     #size-cells = <1>;
+///     chosen { bootargs = "console=serial0"; };
+///     memory@0 { reg = <0x0 0x3b400000>; };
+///     soc {
+///         #address-cells = <1>;
+///         #size-cells = <1>;
+///         ranges = <0x7e000000 0x3f000000 0x1000000>;
+///         gpio@7e200000 { reg = <0x7e200000 0xb4>; };
+///     };
+/// };
+/// ```
+static DTB: [u8; 367] = [
+    // Header.
+    0xd0, 0x0d, 0xfe, 0xed, // magic
+    0x00, 0x00, 0x01, 0x6f, // totalsize
+    0x00, 0x00, 0x00, 0x48, // off_dt_struct
+    0x00, 0x00, 0x01, 0x40, // off_dt_strings
+    0x00, 0x00, 0x00, 0x28, // off_mem_rsvmap
+    0x00, 0x00, 0x00, 0x11, // version
+    0x00, 0x00, 0x00, 0x10, // last_comp_version
+    0x00, 0x00, 0x00, 0x00, // boot_cpuid_phys
+    0x00, 0x00, 0x00, 0x2f, // size_dt_strings
+    0x00, 0x00, 0x00, 0xf8, // size_dt_struct
+    // Memory reservation block.
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, // address
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, // size
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // address
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // size
+    // Structure block.
+    0x00, 0x00, 0x00, 0x01, // BEGIN_NODE ""
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x03, // PROP #address-cells = <1>
+    0x00, 0x00, 0x00, 0x04,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x03, // PROP #size-cells = <1>
+    0x00, 0x00, 0x00, 0x04,
+    0x00, 0x00, 0x00, 0x0f,
+    0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x01, // BEGIN_NODE "chosen"
+    0x63, 0x68, 0x6f, 0x73,
+    0x65, 0x6e, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x03, // PROP bootargs = "console=serial0"
+    0x00, 0x00, 0x00, 0x10,
+    0x00, 0x00, 0x00, 0x1b,
+    0x63, 0x6f, 0x6e, 0x73,
+    0x6f, 0x6c, 0x65, 0x3d,
+    0x73, 0x65, 0x72, 0x69,
+    0x61, 0x6c, 0x30, 0x00,
+    0x00, 0x00, 0x00, 0x02, // END_NODE
+    0x00, 0x00, 0x00, 0x01, // BEGIN_NODE "memory@0"
+    0x6d, 0x65, 0x6d, 0x6f,
+    0x72, 0x79, 0x40, 0x30,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x03, // PROP reg = <0x0 0x3b400000>
+    0x00, 0x00, 0x00, 0x08,
+    0x00, 0x00, 0x00, 0x24,
+    0x00, 0x00, 0x00, 0x00,
+    0x3b, 0x40, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x02, // END_NODE
+    0x00, 0x00, 0x00, 0x04, // NOP
+    0x00, 0x00, 0x00, 0x01, // BEGIN_NODE "soc"
+    0x73, 0x6f, 0x63, 0x00,
+    0x00, 0x00, 0x00, 0x03, // PROP #address-cells = <1>
+    0x00, 0x00, 0x00, 0x04,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x03, // PROP #size-cells = <1>
+    0x00, 0x00, 0x00, 0x04,
+    0x00, 0x00, 0x00, 0x0f,
+    0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x03, // PROP ranges = <0x7e000000 0x3f000000 0x1000000>
+    0x00, 0x00, 0x00, 0x0c,
+    0x00, 0x00, 0x00, 0x28,
+    0x7e, 0x00, 0x00, 0x00,
+    0x3f, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x01, // BEGIN_NODE "gpio@7e200000"
+    0x67, 0x70, 0x69, 0x6f,
+    0x40, 0x37, 0x65, 0x32,
+    0x30, 0x30, 0x30, 0x30,
+    0x30, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x03, // PROP reg = <0x7e200000 0xb4>
+    0x00, 0x00, 0x00, 0x08,
+    0x00, 0x00, 0x00, 0x24,
+    0x7e, 0x20, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0xb4,
+    0x00, 0x00, 0x00, 0x02, // END_NODE
+    0x00, 0x00, 0x00, 0x02, // END_NODE
+    0x00, 0x00, 0x00, 0x02, // END_NODE
+    0x00, 0x00, 0x00, 0x09, // END
+    // Strings block.
+    0x23, 0x61, 0x64, 0x64, 0x72, 0x65, 0x73, 0x73, // "#address-cells"
+    0x2d, 0x63, 0x65, 0x6c, 0x6c, 0x73, 0x00,
+    0x23, 0x73, 0x69, 0x7a, 0x65, 0x2d, 0x63, 0x65, // "#size-cells"
+    0x6c, 0x6c, 0x73, 0x00,
+    0x62, 0x6f, 0x6f, 0x74, 0x61, 0x72, 0x67, 0x73, // "bootargs"
+    0x00,
+    0x72, 0x65, 0x67, 0x00, // "reg"
+    0x72, 0x61, 0x6e, 0x67, 0x65, 0x73, 0x00, // "ranges"
+];
+
+fn fdt() -> Fdt {
+    Fdt::from_bytes(&DTB).expect("valid device tree")
+}
+
+#[test]
+fn header() {
+    let fdt = fdt();
+    assert_eq!(fdt.size(), DTB.len());
+    assert_eq!(fdt.addr(), DTB.as_ptr() as usize);
+
+    assert!(Fdt::from_bytes(&[]).is_none(), "empty blob");
+    assert!(Fdt::from_bytes(&DTB[4..]).is_none(), "bad magic");
+    assert!(Fdt::from_bytes(&DTB[..DTB.len() - 1]).is_none(), "truncated blob");
+}
+
+#[test]
+fn nested_nodes() {
+    let expected = [
+        ("", 0),
+        ("chosen", 1),
+        ("memory@0", 1),
+        ("soc", 1),
+        ("gpio@7e200000", 2),
+    ];
+
+    let nodes = fdt().nodes().map(|node| (node.name(), node.depth()));
+    assert!(nodes.eq(expected.iter().cloned()));
+}
+
+#[test]
+fn find() {
+    let fdt = fdt();
+    assert_eq!(fdt.find("/").map(|node| node.name()), Some(""));
+    assert_eq!(fdt.find("/memory").map(|node| node.name()), Some("memory@0"));
+    assert_eq!(fdt.find("/soc/gpio").map(|node| node.name()), Some("gpio@7e200000"));
+    assert_eq!(
+        fdt.find("/soc/gpio@7e200000").map(|node| node.depth()),
+        Some(2)
+    );
+
+    assert!(fdt.find("/gpio").is_none(), "matched a nested node at the top");
+    assert!(fdt.find("/soc/gpio@0").is_none(), "matched the wrong unit address");
+    assert!(fdt.find("/chosen/bootargs").is_none(), "matched a property");
+}
+
+#[test]
+fn properties() {
+    let fdt = fdt();
+
+    // Property names come from the strings block.
+    let root = fdt.find("/").unwrap();
+    let names = root.properties().map(|prop| prop.name);
+    assert!(names.eq(["#address-cells", "#size-cells"].iter().cloned()));
+    assert_eq!(root.property("#size-cells").and_then(|p| p.as_u32()), Some(1));
+    assert!(root.property("bootargs").is_none(), "found a child's property");
+
+    let bootargs = fdt.find("/chosen").unwrap().property("bootargs").unwrap();
+    assert_eq!(bootargs.value, b"console=serial0\0");
+    assert_eq!(bootargs.as_str(), Some("console=serial0"));
+    assert_eq!(bootargs.as_u32(), None);
+    assert_eq!(fdt.bootargs(), Some("console=serial0"));
+
+    let gpio = fdt.find("/soc/gpio").unwrap();
+    assert!(gpio.reg().unwrap().eq(Some((0x7e200000, 0xb4))));
+}
+
+#[test]
+fn memory_and_peripherals() {
+    let fdt = fdt();
+    assert!(fdt.memory().unwrap().eq(Some((0, 0x3b400000))));
+    assert_eq!(fdt.peripherals(), Some((0x3f000000, 0x1000000)));
+}
+
+#[test]
+fn reservations() {
+    assert!(fdt().reservations().eq(Some((0x1000, 0x2000))));
+}
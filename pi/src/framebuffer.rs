@@ -0,0 +1,170 @@
+use core::ptr;
+
+use mailbox::{self, PropertyMessage, PixelOrder};
+use mailbox::{GetPhysicalSize, SetPhysicalSize, SetVirtualSize, SetVirtualOffset};
+use mailbox::{SetDepth, SetPixelOrder, AllocateBuffer, GetPitch};
+
+/// The number of bits per pixel of a `Framebuffer`.
+const DEPTH: u32 = 32;
+
+/// The alignment in bytes requested for the framebuffer.
+const ALIGNMENT: u32 = 16;
+
+/// Error type for framebuffer allocation failures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A property message to the firmware failed.
+    Mailbox(mailbox::Error),
+    /// The firmware reports no display, or a display of zero size.
+    NoDisplay,
+    /// The firmware didn't allocate a framebuffer.
+    AllocationFailed,
+    /// The firmware allocated a framebuffer with a depth other than 32 bits.
+    UnsupportedDepth(u32),
+}
+
+impl From<mailbox::Error> for Error {
+    fn from(error: mailbox::Error) -> Error {
+        Error::Mailbox(error)
+    }
+}
+
+/// A 32-bit framebuffer allocated by the firmware.
+///
+/// Pixels are `u32` colours as returned by `color()`. Coordinates outside the
+/// framebuffer are ignored.
+pub struct Framebuffer {
+    /// The ARM physical address of the first pixel.
+    base: usize,
+    width: u32,
+    height: u32,
+    /// The number of bytes per line.
+    pitch: usize,
+    order: PixelOrder,
+}
+
+impl Framebuffer {
+    /// Allocates a framebuffer of `width` by `height` pixels and sets the
+    /// display to that size. If either is 0, the display's current size is
+    /// used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoDisplay` if no size is given and the display has
+    /// none, for instance because no monitor is connected.
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, Error> {
+        let (width, height) = match (width, height) {
+            (0, _) | (_, 0) => mailbox::request(&GetPhysicalSize)?,
+            size => size,
+        };
+
+        if width == 0 || height == 0 {
+            return Err(Error::NoDisplay);
+        }
+
+        let mut message = PropertyMessage::new();
+        message.push(&SetPhysicalSize { width, height })?;
+        let size = message.push(&SetVirtualSize { width, height })?;
+        message.push(&SetVirtualOffset { x: 0, y: 0 })?;
+        let depth = message.push(&SetDepth { bits: DEPTH })?;
+        let order = message.push(&SetPixelOrder { order: PixelOrder::Rgb })?;
+        let buffer = message.push(&AllocateBuffer { alignment: ALIGNMENT })?;
+        let pitch = message.push(&GetPitch)?;
+        message.send()?;
+
+        let depth = message.get(depth)?;
+        if depth != DEPTH {
+            return Err(Error::UnsupportedDepth(depth));
+        }
+
+        let (address, _) = message.get(buffer)?;
+        if address == 0 {
+            return Err(Error::AllocationFailed);
+        }
+
+        let (width, height) = message.get(size)?;
+        Ok(Framebuffer {
+            base: (address & !mailbox::BUS_ALIAS) as usize,
+            width,
+            height,
+            pitch: message.get(pitch)? as usize,
+            order: message.get(order)?,
+        })
+    }
+
+    /// The width of the framebuffer in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the framebuffer in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the pixel value of the colour with the given red, green and
+    /// blue components.
+    pub fn color(&self, red: u8, green: u8, blue: u8) -> u32 {
+        let (first, third) = match self.order {
+            PixelOrder::Rgb => (red, blue),
+            PixelOrder::Bgr => (blue, red),
+        };
+
+        first as u32 | (green as u32) << 8 | (third as u32) << 16
+    }
+
+    /// Returns a pointer to the pixel at (`x`, `y`), which must be in bounds.
+    fn pixel(&self, x: u32, y: u32) -> *mut u32 {
+        (self.base + y as usize * self.pitch + x as usize * 4) as *mut u32
+    }
+
+    /// Sets the pixel at (`x`, `y`) to `color`.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: u32) {
+        if x < self.width && y < self.height {
+            unsafe { ptr::write_volatile(self.pixel(x, y), color) }
+        }
+    }
+
+    /// Fills the `width` by `height` rectangle whose top-left corner is at
+    /// (`x`, `y`) with `color`. The rectangle is clipped to the framebuffer.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let right = self.width.min(x.saturating_add(width));
+        let bottom = self.height.min(y.saturating_add(height));
+        for row in y..bottom {
+            let line = self.pixel(0, row);
+            for column in x..right {
+                unsafe { ptr::write_volatile(line.add(column as usize), color) }
+            }
+        }
+    }
+
+    /// Fills the whole framebuffer with `color`.
+    pub fn clear(&mut self, color: u32) {
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, 0, width, height, color);
+    }
+
+    /// Moves the contents of the framebuffer up by `lines` pixel rows and
+    /// fills the rows uncovered at the bottom with `color`.
+    pub fn scroll_up(&mut self, lines: u32, color: u32) {
+        if lines >= self.height {
+            return self.clear(color);
+        }
+
+        let rows = (self.height - lines) as usize;
+        unsafe {
+            ptr::copy(
+                self.pixel(0, lines) as *const u8,
+                self.base as *mut u8,
+                rows * self.pitch,
+            );
+        }
+
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, height - lines, width, lines, color);
+    }
+}
@@ -1,11 +1,14 @@
 use core::marker::PhantomData;
 
 use common::{IO_BASE, states};
+use interrupt::Interrupt;
+use timer;
 use volatile::prelude::*;
 use volatile::{Volatile, WriteVolatile, ReadVolatile, Reserved};
 
 /// An alternative GPIO function.
 #[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Function {
     Input = 0b000,
     Output = 0b001,
@@ -17,6 +20,53 @@ pub enum Function {
     Alt5 = 0b010
 }
 
+impl Function {
+    /// Returns the function selected by the 3-bit `FSEL` field `bits`.
+    fn from_bits(bits: u32) -> Function {
+        match bits & 0b111 {
+            0b000 => Function::Input,
+            0b001 => Function::Output,
+            0b100 => Function::Alt0,
+            0b101 => Function::Alt1,
+            0b110 => Function::Alt2,
+            0b111 => Function::Alt3,
+            0b011 => Function::Alt4,
+            _ => Function::Alt5,
+        }
+    }
+}
+
+/// An event a GPIO input pin can detect.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A low to high transition, sampled with the system clock.
+    RisingEdge,
+    /// A high to low transition, sampled with the system clock.
+    FallingEdge,
+    /// A high level. Detected for as long as the level persists.
+    High,
+    /// A low level. Detected for as long as the level persists.
+    Low,
+    /// A low to high transition, not sampled, so very short pulses are
+    /// detected too.
+    AsyncRisingEdge,
+    /// A high to low transition, not sampled.
+    AsyncFallingEdge,
+}
+
+/// The pull resistor setting of a GPIO pin.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pull {
+    None = 0b00,
+    Down = 0b01,
+    Up = 0b10,
+}
+
+/// Microseconds to wait for a `PUD` or `PUDCLK` write to take effect. The
+/// datasheet asks for 150 cycles, which is well below this.
+const PULL_SETUP_US: u64 = 5;
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -57,7 +107,8 @@ states! {
 /// use solely to mark and track the state of a given GPIO pin. A `Gpio`
 /// structure starts in the `Uninitialized` state and must be transitions into
 /// one of `Input`, `Output`, or `Alt` via the `into_input`, `into_output`, and
-/// `into_alt` methods before it can be used.
+/// `into_alt` methods before it can be used. The same methods reconfigure a
+/// pin that is already in one of those states.
 pub struct Gpio<State> {
     pin: u8,
     registers: &'static mut Registers,
@@ -67,6 +118,11 @@ pub struct Gpio<State> {
 /// The base address of the `GPIO` registers.
 const GPIO_BASE: usize = IO_BASE + 0x200000;
 
+/// Returns the `GPIO` registers.
+fn registers() -> &'static mut Registers {
+    unsafe { &mut *(GPIO_BASE as *mut Registers) }
+}
+
 impl<T> Gpio<T> {
     /// Transitions `self` to state `S`, consuming `self` and returning a new
     /// `Gpio` instance in state `S`. This method should _never_ be exposed to
@@ -79,37 +135,39 @@ impl<T> Gpio<T> {
             _state: PhantomData
         }
     }
-}
 
-impl Gpio<Uninitialized> {
-    /// Returns a new `GPIO` structure for pin number `pin`.
-    ///
-    /// # Panics
-    ///
-    /// Panics if `pin` > `53`.
-    pub fn new(pin: u8) -> Gpio<Uninitialized> {
-        if pin > 53 {
-            panic!("Gpio::new(): pin {} exceeds maximum of 53", pin);
-        }
-
-        Gpio {
-            registers: unsafe { &mut *(GPIO_BASE as *mut Registers) },
-            pin: pin,
-            _state: PhantomData
-        }
+    /// Returns the number of this pin.
+    pub fn pin(&self) -> u8 {
+        self.pin
     }
 
-    /// Enables the alternative function `function` for `self`. Consumes self
-    /// and returns a `Gpio` structure in the `Alt` state.
-    pub fn into_alt(self, function: Function) -> Gpio<Alt> {
-        let fsel_no = (self.pin / 10) as usize;
-        let field_no = self.pin % 10;
+    /// Returns the function currently selected for this pin. This reads the
+    /// hardware, so it reflects changes made by the firmware or through
+    /// another `Gpio` for the same pin.
+    pub fn function(&self) -> Function {
+        let (index, shift) = self.fsel();
+        Function::from_bits(self.registers.FSEL[index].read() >> shift)
+    }
 
-        let init_val = self.registers.FSEL[fsel_no].read();
-        let mask: u32 = (function as u32) << (field_no * 3);
+    /// Returns the index of the `FSEL` register holding this pin's function
+    /// field and the field's offset in it.
+    fn fsel(&self) -> (usize, u32) {
+        ((self.pin / 10) as usize, (self.pin % 10) as u32 * 3)
+    }
 
-        self.registers.FSEL[fsel_no].write(init_val | mask);
+    /// Selects `function` for this pin, leaving other pins' functions as they
+    /// were.
+    fn set_function(&mut self, function: Function) {
+        let (index, shift) = self.fsel();
+        let fsel = &mut self.registers.FSEL[index];
+        let value = fsel.read() & !(0b111 << shift);
+        fsel.write(value | (function as u32) << shift);
+    }
 
+    /// Enables the alternative function `function` for `self`. Consumes self
+    /// and returns a `Gpio` structure in the `Alt` state.
+    pub fn into_alt(mut self, function: Function) -> Gpio<Alt> {
+        self.set_function(function);
         self.transition()
     }
 
@@ -124,6 +182,31 @@ impl Gpio<Uninitialized> {
     pub fn into_input(self) -> Gpio<Input> {
         self.into_alt(Function::Input).transition()
     }
+
+    /// Returns the index of the register holding this pin's bit in
+    /// two-register banks such as `SET` and `EDS`, and the bit itself.
+    fn bank(&self) -> (usize, u32) {
+        ((self.pin / 32) as usize, 1 << (self.pin % 32))
+    }
+}
+
+impl Gpio<Uninitialized> {
+    /// Returns a new `GPIO` structure for pin number `pin`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pin` > `53`.
+    pub fn new(pin: u8) -> Gpio<Uninitialized> {
+        if pin > 53 {
+            panic!("Gpio::new(): pin {} exceeds maximum of 53", pin);
+        }
+
+        Gpio {
+            registers: registers(),
+            pin: pin,
+            _state: PhantomData
+        }
+    }
 }
 
 impl Gpio<Output> {
@@ -156,4 +239,93 @@ impl Gpio<Input> {
             self.registers.LEV[1].read() & (1 << (self.pin - 32)) != 0
         }
     }
+
+    /// Sets the pull resistor of this pin to `pull`, using the `PUD` and
+    /// `PUDCLK` sequence from the datasheet. The setting is kept until it is
+    /// changed again, even if the pin's function changes.
+    pub fn set_pull(&mut self, pull: Pull) {
+        let (index, bit) = self.bank();
+        self.registers.PUD.write(pull as u32);
+        timer::spin_sleep_us(PULL_SETUP_US);
+        self.registers.PUDCLK[index].write(bit);
+        timer::spin_sleep_us(PULL_SETUP_US);
+        self.registers.PUD.write(Pull::None as u32);
+        self.registers.PUDCLK[index].write(0);
+    }
+
+    /// Returns the event detect enable registers for `event`.
+    fn event_enable(&mut self, event: Event) -> &mut [Volatile<u32>; 2] {
+        match event {
+            Event::RisingEdge => &mut self.registers.REN,
+            Event::FallingEdge => &mut self.registers.FEN,
+            Event::High => &mut self.registers.HEN,
+            Event::Low => &mut self.registers.LEN,
+            Event::AsyncRisingEdge => &mut self.registers.AREN,
+            Event::AsyncFallingEdge => &mut self.registers.AFEN,
+        }
+    }
+
+    /// Enables detection of `event` on this pin. Detected events are flagged
+    /// until cleared with `clear_event()` and raise `interrupt()`.
+    pub fn enable_event(&mut self, event: Event) {
+        let (index, bit) = self.bank();
+        self.event_enable(event)[index].or_mask(bit);
+    }
+
+    /// Disables detection of `event` on this pin.
+    pub fn disable_event(&mut self, event: Event) {
+        let (index, bit) = self.bank();
+        self.event_enable(event)[index].and_mask(!bit);
+    }
+
+    /// Disables detection of all events on this pin.
+    pub fn disable_events(&mut self) {
+        for &event in [
+            Event::RisingEdge,
+            Event::FallingEdge,
+            Event::High,
+            Event::Low,
+            Event::AsyncRisingEdge,
+            Event::AsyncFallingEdge,
+        ].iter() {
+            self.disable_event(event);
+        }
+    }
+
+    /// Returns `true` if an enabled event was detected on this pin and not
+    /// yet cleared.
+    pub fn event_detected(&self) -> bool {
+        let (index, bit) = self.bank();
+        self.registers.EDS[index].has_mask(bit)
+    }
+
+    /// Clears a detected event on this pin. Level events are detected again
+    /// immediately if the level persists.
+    pub fn clear_event(&mut self) {
+        let (index, bit) = self.bank();
+        self.registers.EDS[index].write(bit);
+    }
+
+    /// Returns the interrupt raised by events detected on this pin.
+    pub fn interrupt(&self) -> Interrupt {
+        match self.pin {
+            0...27 => Interrupt::Gpio0,
+            28...45 => Interrupt::Gpio1,
+            _ => Interrupt::Gpio2,
+        }
+    }
+}
+
+/// Returns a bit set of the pins with detected events: bit `n` is set if an
+/// event was detected on pin `n`.
+pub fn pending_events() -> u64 {
+    let registers = registers();
+    (registers.EDS[1].read() as u64) << 32 | registers.EDS[0].read() as u64
+}
+
+/// Clears detected events on the pins in the bit set `pins`.
+pub fn clear_events(pins: u64) {
+    let registers = registers();
+    registers.EDS[0].write(pins as u32);
+    registers.EDS[1].write((pins >> 32) as u32);
 }
@@ -0,0 +1,285 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use common::{divider, IO_BASE, CORE_CLOCK_HZ};
+use gpio::{Gpio, Function};
+use mailbox::{self, Clock};
+
+/// The base address for the BSC1 registers.
+const BSC1_REG_BASE: usize = IO_BASE + 0x804000;
+
+/// The I2C clock a new `I2c` runs at: standard mode, 100 kHz.
+pub const DEFAULT_CLOCK_HZ: u32 = 100 * 1000;
+
+/// The number of bytes the FIFO holds.
+const FIFO_SIZE: usize = 16;
+
+/// The largest number of bytes in a single transfer.
+const MAX_LEN: usize = 0xFFFF;
+
+/// The largest clock divider. A `DIV` value of 0 selects it.
+const MAX_DIVIDER: u32 = 32768;
+
+/// Bit fields of the `C` (control) register.
+#[repr(u32)]
+enum Control {
+    Read = 1 << 0,
+    ClearFifo = 0b11 << 4,
+    Start = 1 << 7,
+    Enable = 1 << 15,
+}
+
+/// Bit fields of the `S` (status) register.
+#[repr(u32)]
+enum Status {
+    TransferActive = 1 << 0,
+    Done = 1 << 1,
+    TxData = 1 << 4,
+    RxData = 1 << 5,
+    Nack = 1 << 8,
+    ClockTimeout = 1 << 9,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    C: Volatile<u32>,
+    S: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    A: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    DIV: Volatile<u32>,
+    DEL: Volatile<u32>,
+    CLKT: Volatile<u32>,
+}
+
+/// Error type for I2C configurations and transactions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The address isn't a 7-bit address.
+    InvalidAddress(u8),
+    /// The transfer is longer than the 65535 bytes the controller can send
+    /// or receive at once.
+    TooLong,
+    /// The clock frequency can't be derived from the core clock.
+    UnachievableClock,
+    /// The device didn't acknowledge its address or a byte written to it,
+    /// most likely because there is no device at the address.
+    Nack,
+    /// The device held the clock low for longer than the clock stretch
+    /// timeout.
+    ClockStretchTimeout,
+}
+
+/// The I2C master BSC1, on the header's SDA (GPIO 2) and SCL (GPIO 3) pins,
+/// with polled transfers and 7-bit addresses.
+///
+/// ```rust,ignore
+/// let mut i2c = I2c::new();
+/// let mut time = [0; 7];
+/// i2c.write_read(0x68, &[0x00], &mut time)?;
+/// ```
+pub struct I2c {
+    registers: &'static mut Registers,
+    /// The frequency of the core clock in Hz.
+    clock: u32,
+}
+
+impl I2c {
+    /// Initializes BSC1 by setting GPIO pins 2 and 3 to alternative function
+    /// 0 (SDA1 and SCL1). The clock runs at `DEFAULT_CLOCK_HZ`. The pins
+    /// have pull-up resistors on the board.
+    pub fn new() -> I2c {
+        Gpio::new(2).into_alt(Function::Alt0);
+        Gpio::new(3).into_alt(Function::Alt0);
+
+        let mut i2c = I2c {
+            registers: unsafe { &mut *(BSC1_REG_BASE as *mut Registers) },
+            clock: mailbox::clock_rate(Clock::Core).unwrap_or(CORE_CLOCK_HZ),
+        };
+
+        i2c.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        i2c.clear_status();
+        i2c.set_clock(DEFAULT_CLOCK_HZ).expect("default I2C clock is achievable");
+        i2c
+    }
+
+    /// Sets the I2C clock to the fastest rate no faster than `hz` that can be
+    /// derived from the core clock. Returns that rate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableClock` if `hz` is 0 or below the slowest
+    /// rate. The clock is then unchanged.
+    pub fn set_clock(&mut self, hz: u32) -> Result<u32, Error> {
+        let divider = divider(self.clock, hz, MAX_DIVIDER).ok_or(Error::UnachievableClock)?;
+        self.registers.DIV.write(divider % MAX_DIVIDER);
+        Ok(self.clock / divider)
+    }
+
+    /// Sets the number of I2C clock cycles a device may hold the clock low
+    /// before a transfer fails with `Error::ClockStretchTimeout`. A timeout
+    /// of 0 lets devices stretch the clock indefinitely.
+    pub fn set_clock_stretch_timeout(&mut self, cycles: u16) {
+        self.registers.CLKT.write(cycles as u32);
+    }
+
+    /// Writes `data` to the device at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Nack` if the device doesn't acknowledge its address or
+    /// a byte. See `Error` for the other errors.
+    pub fn write(&mut self, address: u8, data: &[u8]) -> Result<(), Error> {
+        self.start(address, data.len(), false)?;
+        self.send(data)?;
+        self.finish()
+    }
+
+    /// Reads `buf.len()` bytes from the device at `address` into `buf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Nack` if the device doesn't acknowledge its address.
+    /// See `Error` for the other errors.
+    pub fn read(&mut self, address: u8, buf: &mut [u8]) -> Result<(), Error> {
+        self.start(address, buf.len(), true)?;
+        self.receive(buf)?;
+        self.finish()
+    }
+
+    /// Writes `data` to the device at `address`, then reads `buf.len()`
+    /// bytes from it into `buf`, as to read a device register.
+    ///
+    /// If `data` fits in the FIFO (16 bytes), the read follows the write with
+    /// a repeated start, as devices that keep state between the two expect.
+    /// Otherwise, the write is stopped before the read starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Nack` if the device doesn't acknowledge its address or
+    /// a byte written. See `Error` for the other errors.
+    pub fn write_read(&mut self, address: u8, data: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+        if data.len() > FIFO_SIZE {
+            self.write(address, data)?;
+            return self.read(address, buf);
+        }
+
+        if buf.len() > MAX_LEN {
+            return Err(Error::TooLong);
+        }
+
+        // The controller can't be told to skip the stop condition. Instead,
+        // the read is started once the write is under way, which makes the
+        // controller follow it with a start rather than a stop.
+        self.start(address, data.len(), false)?;
+        self.send(data)?;
+        let started = Status::TransferActive as u32 | Status::Done as u32;
+        while self.registers.S.read() & started == 0 {
+            self.check()?;
+        }
+
+        self.registers.DLEN.write(buf.len() as u32);
+        self.registers.C.write(
+            Control::Enable as u32 | Control::Start as u32 | Control::Read as u32,
+        );
+        self.receive(buf)?;
+        self.finish()
+    }
+
+    /// Clears the FIFO and the status flags and starts a transfer of `len`
+    /// bytes to or from the device at `address`.
+    fn start(&mut self, address: u8, len: usize, read: bool) -> Result<(), Error> {
+        if address > 0x7F {
+            return Err(Error::InvalidAddress(address));
+        }
+
+        if len > MAX_LEN {
+            return Err(Error::TooLong);
+        }
+
+        self.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        self.clear_status();
+        self.registers.A.write(address as u32);
+        self.registers.DLEN.write(len as u32);
+
+        let mut control = Control::Enable as u32 | Control::Start as u32;
+        if read {
+            control |= Control::Read as u32;
+        }
+        self.registers.C.write(control);
+        Ok(())
+    }
+
+    /// Feeds `data` to the FIFO as space becomes available.
+    fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        for &byte in data {
+            while !self.registers.S.has_mask(Status::TxData as u32) {
+                self.check()?;
+            }
+
+            self.registers.FIFO.write(byte as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Fills `buf` from the FIFO as bytes arrive.
+    fn receive(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for byte in buf.iter_mut() {
+            while !self.registers.S.has_mask(Status::RxData as u32) {
+                self.check()?;
+            }
+
+            *byte = self.registers.FIFO.read() as u8;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the transfer to end and clears the status flags. The
+    /// transfer is active until the stop condition was sent, even if the
+    /// `Done` flag was set by the write of a `write_read()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that ended the transfer, if any.
+    fn finish(&mut self) -> Result<(), Error> {
+        loop {
+            let status = self.registers.S.read();
+            if status & Status::Done as u32 != 0 && status & Status::TransferActive as u32 == 0 {
+                break;
+            }
+
+            self.check()?;
+        }
+
+        self.check()?;
+        self.clear_status();
+        Ok(())
+    }
+
+    /// Returns the error flagged for the current transfer, if any. The
+    /// transfer is then over, so the FIFO and status flags are cleared.
+    fn check(&mut self) -> Result<(), Error> {
+        let status = self.registers.S.read();
+        let error = if status & Status::Nack as u32 != 0 {
+            Error::Nack
+        } else if status & Status::ClockTimeout as u32 != 0 {
+            Error::ClockStretchTimeout
+        } else {
+            return Ok(());
+        };
+
+        self.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        self.clear_status();
+        Err(error)
+    }
+
+    /// Clears the `Done`, `Nack` and `ClockTimeout` status flags.
+    fn clear_status(&mut self) {
+        self.registers.S.write(
+            Status::Done as u32 | Status::Nack as u32 | Status::ClockTimeout as u32,
+        );
+    }
+}
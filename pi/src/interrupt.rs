@@ -1,31 +1,173 @@
 use common::IO_BASE;
 use volatile::prelude::*;
-use volatile::{Volatile, ReadVolatile, Reserved};
+use volatile::{Volatile, ReadVolatile, WriteVolatile, Reserved};
 
 const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
 
-#[derive(Copy, Clone, PartialEq)]
+/// The bits of the basic pending register set when a bit of the first or
+/// second pending register is.
+const BASIC_PENDING_1: u32 = 1 << 8;
+const BASIC_PENDING_2: u32 = 1 << 9;
+
+/// The bits of the basic pending register for the ARM's own interrupts: the
+/// ARM timer, the ARM mailbox, the doorbells and so on.
+const BASIC_ARM_MASK: u32 = 0xFF;
+
+/// The first bit of the basic pending register that repeats one of the
+/// interrupts in `SHORTCUTS`. The repeated interrupts don't set the
+/// `BASIC_PENDING_1` and `BASIC_PENDING_2` bits.
+const SHORTCUT_SHIFT: u32 = 10;
+
+/// The interrupts repeated in the basic pending register, in bit order.
+const SHORTCUTS: [u32; 11] = [7, 9, 10, 18, 19, 53, 54, 55, 56, 57, 62];
+
+/// The bit of the FIQ control register that enables the FIQ. The lower bits
+/// select its source.
+const FIQ_ENABLE: u32 = 1 << 7;
+
+/// The base address of the BCM2836 core-local interrupt controller (QA7).
+const LOCAL_INT_BASE: usize = 0x4000_0000;
+
+/// The number of interrupts: 64 GPU interrupts followed by the 8 ARM
+/// interrupts of the basic pending register.
+pub const NUM_INTERRUPTS: usize = 72;
+
+/// An interrupt of the interrupt controller. The first 64 are the GPU's
+/// interrupts, shared with the VideoCore. The rest are the ARM's own.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Interrupt {
+    /// System timer channel 0, which the GPU firmware uses.
+    Timer0 = 0,
     Timer1 = 1,
+    /// System timer channel 2, which the GPU firmware uses.
+    Timer2 = 2,
     Timer3 = 3,
+    Codec0 = 4,
+    Codec1 = 5,
+    Codec2 = 6,
+    Jpeg = 7,
+    Isp = 8,
     Usb = 9,
+    V3d = 10,
+    Transposer = 11,
+    MulticoreSync0 = 12,
+    MulticoreSync1 = 13,
+    MulticoreSync2 = 14,
+    MulticoreSync3 = 15,
+    // DMA channels 0 through 10 have an interrupt each.
+    Dma0 = 16,
+    Dma1 = 17,
+    Dma2 = 18,
+    Dma3 = 19,
+    Dma4 = 20,
+    Dma5 = 21,
+    Dma6 = 22,
+    Dma7 = 23,
+    Dma8 = 24,
+    Dma9 = 25,
+    Dma10 = 26,
+    /// The DMA channels 11 through 14, which share an interrupt.
+    DmaShared = 27,
+    /// All DMA channels.
+    DmaAll = 28,
+    /// The auxiliary peripherals: the mini UART and the SPI1 and SPI2
+    /// masters.
+    Aux = 29,
+    Arm = 30,
+    VpuDma = 31,
+    HostPort = 32,
+    VideoScaler = 33,
+    Ccp2tx = 34,
+    Sdc = 35,
+    Dsi0 = 36,
+    Ave = 37,
+    Cam0 = 38,
+    Cam1 = 39,
+    Hdmi0 = 40,
+    Hdmi1 = 41,
+    PixelValve1 = 42,
+    I2cSpiSlave = 43,
+    Dsi1 = 44,
+    Pwa0 = 45,
+    Pwa1 = 46,
+    Cpr = 47,
+    Smi = 48,
     Gpio0 = 49,
     Gpio1 = 50,
     Gpio2 = 51,
     Gpio3 = 52,
+    /// The BSC (I2C) masters.
+    I2c = 53,
+    /// SPI0.
+    Spi = 54,
+    Pcm = 55,
+    /// The SD host controller.
+    SdHost = 56,
+    /// The PL011 UART.
     Uart = 57,
+    Slimbus = 58,
+    Vec = 59,
+    Cpg = 60,
+    Rng = 61,
+    /// The Arasan EMMC controller.
+    Emmc = 62,
+    AvsPmon = 63,
+    /// The ARM timer, the first of the ARM's own interrupts in the basic
+    /// pending register.
+    ArmTimer = 64,
+    ArmMailbox = 65,
+    Doorbell0 = 66,
+    Doorbell1 = 67,
+    Gpu0Halted = 68,
+    Gpu1Halted = 69,
+    IllegalAccess1 = 70,
+    IllegalAccess0 = 71,
+}
+
+/// All interrupts, indexed by their number.
+const INTERRUPTS: [Interrupt; NUM_INTERRUPTS] = [
+    Interrupt::Timer0, Interrupt::Timer1, Interrupt::Timer2, Interrupt::Timer3, Interrupt::Codec0,
+    Interrupt::Codec1, Interrupt::Codec2, Interrupt::Jpeg, Interrupt::Isp, Interrupt::Usb,
+    Interrupt::V3d, Interrupt::Transposer, Interrupt::MulticoreSync0, Interrupt::MulticoreSync1,
+    Interrupt::MulticoreSync2, Interrupt::MulticoreSync3, Interrupt::Dma0, Interrupt::Dma1,
+    Interrupt::Dma2, Interrupt::Dma3, Interrupt::Dma4, Interrupt::Dma5, Interrupt::Dma6,
+    Interrupt::Dma7, Interrupt::Dma8, Interrupt::Dma9, Interrupt::Dma10, Interrupt::DmaShared,
+    Interrupt::DmaAll, Interrupt::Aux, Interrupt::Arm, Interrupt::VpuDma, Interrupt::HostPort,
+    Interrupt::VideoScaler, Interrupt::Ccp2tx, Interrupt::Sdc, Interrupt::Dsi0, Interrupt::Ave,
+    Interrupt::Cam0, Interrupt::Cam1, Interrupt::Hdmi0, Interrupt::Hdmi1, Interrupt::PixelValve1,
+    Interrupt::I2cSpiSlave, Interrupt::Dsi1, Interrupt::Pwa0, Interrupt::Pwa1, Interrupt::Cpr,
+    Interrupt::Smi, Interrupt::Gpio0, Interrupt::Gpio1, Interrupt::Gpio2, Interrupt::Gpio3,
+    Interrupt::I2c, Interrupt::Spi, Interrupt::Pcm, Interrupt::SdHost, Interrupt::Uart,
+    Interrupt::Slimbus, Interrupt::Vec, Interrupt::Cpg, Interrupt::Rng, Interrupt::Emmc,
+    Interrupt::AvsPmon, Interrupt::ArmTimer, Interrupt::ArmMailbox, Interrupt::Doorbell0,
+    Interrupt::Doorbell1, Interrupt::Gpu0Halted, Interrupt::Gpu1Halted, Interrupt::IllegalAccess1,
+    Interrupt::IllegalAccess0,
+];
+
+impl Interrupt {
+    /// Returns the interrupt with the number `index`, or `None` if `index`
+    /// isn't less than `NUM_INTERRUPTS`.
+    pub fn from_index(index: u32) -> Option<Interrupt> {
+        INTERRUPTS.get(index as usize).cloned()
+    }
+
+    /// Returns `true` if this is one of the ARM's own interrupts, which are
+    /// enabled and reported in the basic registers.
+    pub fn is_basic(self) -> bool {
+        self as usize >= 64
+    }
 }
 
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
-    basic_pending: Reserved<u32>,
+    basic_pending: ReadVolatile<u32>,
     pending: [ReadVolatile<u32>; 2],
-    fiq: Reserved<u32>,
+    fiq: Volatile<u32>,
     enable: [Volatile<u32>; 2],
-    enable_basic: Reserved<u32>,
+    enable_basic: Volatile<u32>,
     disable: [Volatile<u32>; 2],
-    disable_basic: Reserved<u32>,
+    disable_basic: Volatile<u32>,
 }
 
 /// An interrupt controller. Used to enable and disable interrupts as well as to
@@ -47,8 +189,10 @@ impl Controller {
         let index = int as u32;
         if index < 32 {
             self.registers.enable[0].or_mask(1 << index);
-        } else {
+        } else if index < 64 {
             self.registers.enable[1].or_mask(1 << (index - 32));
+        } else {
+            self.registers.enable_basic.or_mask(1 << (index - 64));
         }
     }
 
@@ -57,8 +201,10 @@ impl Controller {
         let index = int as u32;
         if index < 32 {
             self.registers.disable[0].or_mask(1 << index);
-        } else {
+        } else if index < 64 {
             self.registers.disable[1].or_mask(1 << (index - 32));
+        } else {
+            self.registers.disable_basic.or_mask(1 << (index - 64));
         }
     }
 
@@ -67,8 +213,300 @@ impl Controller {
         let index = int as u32;
         if index < 32 {
             self.registers.pending[0].has_mask(1 << index)
+        } else if index < 64 {
+            self.registers.pending[1].has_mask(1 << (index - 32))
+        } else {
+            self.registers.basic_pending.has_mask(1 << (index - 64))
+        }
+    }
+
+    /// Returns the pending interrupts. The basic pending register is read
+    /// once, and the other pending registers only if it reports a pending
+    /// interrupt outside of its shortcuts.
+    pub fn pending(&self) -> Pending {
+        let basic = self.registers.basic_pending.read();
+
+        let mut gpu = 0;
+        for (bit, &index) in SHORTCUTS.iter().enumerate() {
+            if basic & (1 << (SHORTCUT_SHIFT + bit as u32)) != 0 {
+                gpu |= 1 << index;
+            }
+        }
+
+        if basic & BASIC_PENDING_1 != 0 {
+            gpu |= self.registers.pending[0].read() as u64;
+        }
+        if basic & BASIC_PENDING_2 != 0 {
+            gpu |= (self.registers.pending[1].read() as u64) << 32;
+        }
+
+        Pending { gpu, basic: (basic & BASIC_ARM_MASK) as u8 }
+    }
+
+    /// Routes `int` as the FIQ instead of an IRQ, replacing the interrupt
+    /// routed as the FIQ before, if any. `int` must be disabled as an IRQ.
+    pub fn enable_fiq(&mut self, int: Interrupt) {
+        self.registers.fiq.write(int as u32 | FIQ_ENABLE);
+    }
+
+    /// Stops routing any interrupt as the FIQ.
+    pub fn disable_fiq(&mut self) {
+        self.registers.fiq.write(0);
+    }
+}
+
+/// The interrupts pending when `Controller::pending()` was called, iterated
+/// over in order of their numbers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pending {
+    /// Bit `n` is set if GPU interrupt `n` is pending.
+    gpu: u64,
+    /// Bit `n` is set if the ARM interrupt `64 + n` is pending.
+    basic: u8,
+}
+
+impl Pending {
+    /// Returns `true` if no interrupt is pending.
+    pub fn is_empty(&self) -> bool {
+        self.gpu == 0 && self.basic == 0
+    }
+}
+
+impl Iterator for Pending {
+    type Item = Interrupt;
+
+    fn next(&mut self) -> Option<Interrupt> {
+        if self.gpu != 0 {
+            let index = self.gpu.trailing_zeros();
+            self.gpu &= !(1 << index);
+            Interrupt::from_index(index)
+        } else if self.basic != 0 {
+            let index = self.basic.trailing_zeros();
+            self.basic &= !(1 << index);
+            Interrupt::from_index(64 + index)
         } else {
-            self.registers.pending[1].has_mask( 1 << (index - 32))
+            None
         }
     }
 }
+
+/// One of the generic timer's interrupts, numbered as the local interrupt
+/// controller's timer interrupt control and interrupt source bits are.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CoreTimer {
+    /// The secure physical timer, `CNTPS`.
+    PhysicalSecure = 0,
+    /// The non-secure physical timer, `CNTP`, which EL1 and EL0 use.
+    Physical = 1,
+    /// The hypervisor timer, `CNTHP`.
+    Hypervisor = 2,
+    /// The virtual timer, `CNTV`.
+    Virtual = 3,
+}
+
+/// An interrupt source of the local interrupt controller, numbered as the
+/// bits of a core's interrupt source registers are.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LocalInterrupt {
+    PhysicalSecureTimer = 0,
+    PhysicalTimer = 1,
+    HypervisorTimer = 2,
+    VirtualTimer = 3,
+    Mailbox0 = 4,
+    Mailbox1 = 5,
+    Mailbox2 = 6,
+    Mailbox3 = 7,
+    /// An interrupt of the GPU's `Controller`, if routed to the core.
+    Gpu = 8,
+    Pmu = 9,
+    /// Outstanding AXI transactions. Only raised on core 0.
+    Axi = 10,
+    /// The local timer, if routed to the core.
+    LocalTimer = 11,
+}
+
+/// Bit fields of the local timer's control and status register.
+#[repr(u32)]
+enum LocalTimer {
+    ReloadMask = 0x0FFF_FFFF,
+    Enable = 1 << 28,
+    InterruptEnable = 1 << 29,
+    Interrupt = 1 << 31,
+}
+
+/// The bit of the local timer's write flags register that clears its
+/// interrupt.
+const LOCAL_TIMER_CLEAR: u32 = 1 << 31;
+
+/// The frequency the local timer counts down at, in Hz.
+pub const LOCAL_TIMER_HZ: u32 = 38_400_000;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct LocalRegisters {
+    __r0: [Reserved<u32>; 3],
+    GPU_ROUTING: Volatile<u32>,
+    __r1: [Reserved<u32>; 5],
+    LOCAL_ROUTING: Volatile<u32>,
+    __r2: [Reserved<u32>; 3],
+    LOCAL_TIMER_CONTROL: Volatile<u32>,
+    LOCAL_TIMER_FLAGS: WriteVolatile<u32>,
+    __r3: Reserved<u32>,
+    TIMER_CONTROL: [Volatile<u32>; 4],
+    MAILBOX_CONTROL: [Volatile<u32>; 4],
+    IRQ_SOURCE: [ReadVolatile<u32>; 4],
+    FIQ_SOURCE: [ReadVolatile<u32>; 4],
+    MAILBOX_SET: [[WriteVolatile<u32>; 4]; 4],
+    MAILBOX_CLEAR: [[Volatile<u32>; 4]; 4],
+}
+
+/// The core-local interrupt controller, as seen by one core. Each core's
+/// generic timer, mailbox, and local timer interrupts are routed through it,
+/// as are the GPU's interrupts, to a single core.
+///
+/// Each core has four 32-bit mailboxes. Any core may set bits in them to
+/// interrupt the mailboxes' core, which clears the bits once it handled
+/// them.
+pub struct LocalController {
+    core: usize,
+    registers: &'static mut LocalRegisters
+}
+
+impl LocalController {
+    /// Returns a new handle to the local interrupt controller for `core`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` isn't one of the four cores.
+    pub fn new(core: usize) -> LocalController {
+        assert!(core < 4, "interrupt: no core {}", core);
+        LocalController {
+            core,
+            registers: unsafe { &mut *(LOCAL_INT_BASE as *mut LocalRegisters) },
+        }
+    }
+
+    /// Routes `timer`'s interrupt to this core as an IRQ.
+    pub fn enable_timer(&mut self, timer: CoreTimer) {
+        self.registers.TIMER_CONTROL[self.core].or_mask(1 << timer as u32);
+    }
+
+    /// Stops routing `timer`'s interrupt to this core.
+    pub fn disable_timer(&mut self, timer: CoreTimer) {
+        self.registers.TIMER_CONTROL[self.core].and_mask(!(1 << timer as u32));
+    }
+
+    /// Returns `true` if `timer`'s IRQ is pending on this core.
+    pub fn is_timer_pending(&self, timer: CoreTimer) -> bool {
+        self.registers.IRQ_SOURCE[self.core].has_mask(1 << timer as u32)
+    }
+
+    /// Returns `true` if `int` is pending on this core as an IRQ.
+    pub fn is_pending(&self, int: LocalInterrupt) -> bool {
+        self.registers.IRQ_SOURCE[self.core].has_mask(1 << int as u32)
+    }
+
+    /// Returns the IRQs pending on this core as a bit set: bit `n` is set if
+    /// the `LocalInterrupt` numbered `n` is pending.
+    pub fn pending(&self) -> u32 {
+        self.registers.IRQ_SOURCE[self.core].read()
+    }
+
+    /// Returns `true` if `int` is pending on this core as an FIQ.
+    pub fn is_fiq_pending(&self, int: LocalInterrupt) -> bool {
+        self.registers.FIQ_SOURCE[self.core].has_mask(1 << int as u32)
+    }
+
+    /// Routes the GPU's interrupts to this core as IRQs. They are routed to
+    /// core 0 on reset.
+    pub fn route_gpu(&mut self) {
+        let routing = self.registers.GPU_ROUTING.read() & !0b11;
+        self.registers.GPU_ROUTING.write(routing | self.core as u32);
+    }
+
+    /// Routes the GPU's FIQ to this core. It is routed to core 0 on reset.
+    pub fn route_gpu_fiq(&mut self) {
+        let routing = self.registers.GPU_ROUTING.read() & !0b1100;
+        self.registers.GPU_ROUTING.write(routing | (self.core as u32) << 2);
+    }
+
+    /// Raises an IRQ on this core when any bit of its mailbox `mailbox` is
+    /// set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mailbox` isn't one of the four mailboxes.
+    pub fn enable_mailbox(&mut self, mailbox: usize) {
+        assert!(mailbox < 4, "interrupt: no mailbox {}", mailbox);
+        self.registers.MAILBOX_CONTROL[self.core].or_mask(1 << mailbox);
+    }
+
+    /// Stops raising IRQs for this core's mailbox `mailbox`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mailbox` isn't one of the four mailboxes.
+    pub fn disable_mailbox(&mut self, mailbox: usize) {
+        assert!(mailbox < 4, "interrupt: no mailbox {}", mailbox);
+        self.registers.MAILBOX_CONTROL[self.core].and_mask(!(1 << mailbox));
+    }
+
+    /// Sets `bits` in `core`'s mailbox `mailbox`, interrupting `core` if the
+    /// mailbox's interrupt is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` or `mailbox` are out of range.
+    pub fn send(&mut self, core: usize, mailbox: usize, bits: u32) {
+        self.registers.MAILBOX_SET[core][mailbox].write(bits);
+    }
+
+    /// Returns the bits set in this core's mailbox `mailbox`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mailbox` isn't one of the four mailboxes.
+    pub fn read_mailbox(&self, mailbox: usize) -> u32 {
+        self.registers.MAILBOX_CLEAR[self.core][mailbox].read()
+    }
+
+    /// Clears `bits` in this core's mailbox `mailbox`. The mailbox's IRQ is
+    /// pending until all of its bits are clear.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mailbox` isn't one of the four mailboxes.
+    pub fn clear_mailbox(&mut self, mailbox: usize, bits: u32) {
+        self.registers.MAILBOX_CLEAR[self.core][mailbox].write(bits);
+    }
+
+    /// Starts the local timer, which counts down from `reload` at
+    /// `LOCAL_TIMER_HZ`, raises its interrupt, and reloads. The interrupt is
+    /// routed to this core as an IRQ.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reload` is 0 or doesn't fit in 28 bits.
+    pub fn start_local_timer(&mut self, reload: u32) {
+        assert!(reload != 0 && reload <= LocalTimer::ReloadMask as u32,
+                "interrupt: local timer reload value {:#x} out of range", reload);
+
+        self.registers.LOCAL_ROUTING.write(self.core as u32);
+        self.registers.LOCAL_TIMER_CONTROL.write(
+            reload | LocalTimer::Enable as u32 | LocalTimer::InterruptEnable as u32,
+        );
+    }
+
+    /// Stops the local timer.
+    pub fn stop_local_timer(&mut self) {
+        self.registers.LOCAL_TIMER_CONTROL.write(0);
+        self.clear_local_timer();
+    }
+
+    /// Clears the local timer's interrupt. Returns `true` if it was pending.
+    pub fn clear_local_timer(&mut self) -> bool {
+        let pending = self.registers.LOCAL_TIMER_CONTROL.has_mask(LocalTimer::Interrupt as u32);
+        self.registers.LOCAL_TIMER_FLAGS.write(LOCAL_TIMER_CLEAR);
+        pending
+    }
+}
@@ -15,7 +15,17 @@ extern crate volatile;
 
 pub mod timer;
 pub mod uart;
+pub mod pl011;
 pub mod gpio;
 pub mod common;
 pub mod atags;
+pub mod fdt;
 pub mod interrupt;
+pub mod mailbox;
+pub mod framebuffer;
+pub mod pwm;
+pub mod spi;
+pub mod i2c;
+pub mod dma;
+
+mod ring_buffer;
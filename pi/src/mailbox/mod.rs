@@ -0,0 +1,247 @@
+mod tag;
+
+use core::marker::PhantomData;
+use core::{mem, ptr};
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, WriteVolatile, Reserved};
+
+use common::{clean_invalidate_dcache, CACHE_LINE_SIZE, IO_BASE};
+
+pub use self::tag::*;
+
+/// The base address for the mailbox registers.
+const MAILBOX_BASE: usize = IO_BASE + 0xB880;
+
+/// The VideoCore sees ARM physical memory through this uncached alias.
+pub(crate) const BUS_ALIAS: u32 = 0xC000_0000;
+
+/// Bit fields of the mailbox `STATUS` registers.
+#[repr(u32)]
+enum Status {
+    Full = 1 << 31,
+    Empty = 1 << 30,
+}
+
+/// The response code of a successfully processed property message.
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+
+/// The bit set in a tag's value size field once the firmware responded.
+const TAG_RESPONSE: u32 = 1 << 31;
+
+/// The size of a property message buffer in 32-bit words: 1KiB, a whole
+/// number of cache lines.
+const BUFFER_WORDS: usize = 16 * CACHE_LINE_SIZE / 4;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Mailbox 0: VideoCore to ARM.
+    READ: ReadVolatile<u32>,
+    __r0: [Reserved<u32>; 3],
+    PEEK: ReadVolatile<u32>,
+    SENDER: ReadVolatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONFIG: Volatile<u32>,
+    // Mailbox 1: ARM to VideoCore.
+    WRITE: WriteVolatile<u32>,
+    __r1: [Reserved<u32>; 5],
+    WRITE_STATUS: ReadVolatile<u32>,
+}
+
+/// A mailbox channel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    /// Property tags from the ARM to the VideoCore.
+    Property = 8,
+}
+
+/// A clock managed by the firmware.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// The mailbox used to talk to the VideoCore GPU's firmware.
+pub struct Mailbox {
+    registers: &'static mut Registers,
+}
+
+impl Mailbox {
+    /// Returns a new handle to the mailbox.
+    pub fn new() -> Mailbox {
+        Mailbox {
+            registers: unsafe { &mut *(MAILBOX_BASE as *mut Registers) },
+        }
+    }
+
+    /// Sends `data` on `channel` and blocks until the response on the same
+    /// channel arrives. Returns the response's data. Only the upper 28 bits of
+    /// `data` are sent; the lower 4 carry the channel.
+    pub fn call(&mut self, channel: Channel, data: u32) -> u32 {
+        while self.registers.WRITE_STATUS.has_mask(Status::Full as u32) {}
+        self.registers.WRITE.write((data & !0xF) | channel as u32);
+
+        loop {
+            while self.registers.STATUS.has_mask(Status::Empty as u32) {}
+            let response = self.registers.READ.read();
+            if response & 0xF == channel as u32 {
+                return response & !0xF;
+            }
+        }
+    }
+}
+
+/// Error type for property messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The tag doesn't fit in the message buffer.
+    BufferFull,
+    /// The firmware couldn't parse the message.
+    Failed,
+    /// The firmware didn't process the tag, likely because it doesn't know it.
+    Unsupported,
+    /// The firmware's response was larger than the tag's value buffer.
+    Truncated,
+}
+
+/// A property message buffer. The VideoCore requires it to be 16-byte
+/// aligned, as only the upper 28 bits of its address are sent. It's aligned
+/// to `CACHE_LINE_SIZE` and fills whole cache lines, so the cache
+/// maintenance around a call never touches other data sharing a line with
+/// it, which could write stale bytes over the reply.
+#[repr(C, align(64))]
+struct Buffer([u32; BUFFER_WORDS]);
+
+/// A handle to the response of a tag pushed onto a `PropertyMessage`.
+pub struct Slot<T: Tag> {
+    offset: usize,
+    _tag: PhantomData<T>,
+}
+
+/// A message of property tags for the firmware, built up with `push()`, sent
+/// with `send()` and then read back with `get()`.
+///
+/// ```rust,ignore
+/// let mut message = PropertyMessage::new();
+/// let revision = message.push(&GetBoardRevision)?;
+/// let serial = message.push(&GetBoardSerial)?;
+/// message.send()?;
+/// let (revision, serial) = (message.get(revision)?, message.get(serial)?);
+/// ```
+pub struct PropertyMessage {
+    buffer: Buffer,
+    /// The number of words in use, excluding the end tag.
+    len: usize,
+}
+
+impl PropertyMessage {
+    /// Returns a new message with no tags.
+    pub fn new() -> PropertyMessage {
+        PropertyMessage {
+            buffer: Buffer([0; BUFFER_WORDS]),
+            len: 2,
+        }
+    }
+
+    /// Appends `tag` to the message. Returns a slot to read its response
+    /// from once the message was sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferFull` if the tag doesn't fit in the message.
+    pub fn push<T: Tag>(&mut self, tag: &T) -> Result<Slot<T>, Error> {
+        let words = (T::SIZE + 3) / 4;
+        let offset = self.len;
+        // The tag's header and value, then the end tag.
+        if offset + 3 + words + 1 > BUFFER_WORDS {
+            return Err(Error::BufferFull);
+        }
+
+        let buffer = &mut self.buffer.0;
+        buffer[offset] = T::ID;
+        buffer[offset + 1] = T::SIZE as u32;
+        buffer[offset + 2] = 0;
+        tag.encode(&mut buffer[offset + 3..offset + 3 + words]);
+
+        self.len += 3 + words;
+        Ok(Slot {
+            offset,
+            _tag: PhantomData,
+        })
+    }
+
+    /// Sends the message to the firmware and blocks until it responds.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Failed` if the firmware couldn't parse the message.
+    pub fn send(&mut self) -> Result<(), Error> {
+        let len = self.len;
+        self.buffer.0[len] = 0;
+        self.buffer.0[0] = ((len + 1) * 4) as u32;
+        self.buffer.0[1] = 0;
+
+        // The firmware reads the message from memory, bypassing the ARM's
+        // caches, and writes the response into the buffer behind the
+        // compiler's back.
+        let address = self.buffer.0.as_ptr() as usize;
+        let size = mem::size_of_val(&self.buffer.0);
+        compiler_fence(Ordering::SeqCst);
+        clean_invalidate_dcache(address, size);
+        Mailbox::new().call(Channel::Property, address as u32 | BUS_ALIAS);
+        clean_invalidate_dcache(address, size);
+        compiler_fence(Ordering::SeqCst);
+
+        match unsafe { ptr::read_volatile(&self.buffer.0[1]) } {
+            RESPONSE_SUCCESS => Ok(()),
+            _ => Err(Error::Failed),
+        }
+    }
+
+    /// Returns the response to the tag in `slot`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Unsupported` if the firmware didn't process the tag
+    /// and `Error::Truncated` if its response didn't fit.
+    pub fn get<T: Tag>(&self, slot: Slot<T>) -> Result<T::Response, Error> {
+        let indicator = unsafe { ptr::read_volatile(&self.buffer.0[slot.offset + 2]) };
+        if indicator & TAG_RESPONSE == 0 {
+            return Err(Error::Unsupported);
+        }
+
+        if (indicator & !TAG_RESPONSE) as usize > T::SIZE {
+            return Err(Error::Truncated);
+        }
+
+        let start = slot.offset + 3;
+        Ok(T::decode(&self.buffer.0[start..start + (T::SIZE + 3) / 4]))
+    }
+}
+
+/// Sends a message with the single tag `tag` and returns its response.
+pub fn request<T: Tag>(tag: &T) -> Result<T::Response, Error> {
+    let mut message = PropertyMessage::new();
+    let slot = message.push(tag)?;
+    message.send()?;
+    message.get(slot)
+}
+
+/// Returns the rate of `clock` in Hz, as reported by the firmware.
+pub fn clock_rate(clock: Clock) -> Option<u32> {
+    match request(&GetClockRate { clock }) {
+        Ok(0) | Err(_) => None,
+        Ok(rate) => Some(rate),
+    }
+}
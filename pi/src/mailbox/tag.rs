@@ -0,0 +1,501 @@
+use mailbox::Clock;
+
+/// A property tag: a request to the firmware and the response it produces.
+pub trait Tag {
+    /// The decoded response.
+    type Response;
+
+    /// The tag identifier.
+    const ID: u32;
+
+    /// The size in bytes of the tag's value buffer: the larger of the request
+    /// and the response.
+    const SIZE: usize;
+
+    /// Writes the request into `value`, which is `SIZE` bytes rounded up to
+    /// whole words and zeroed. Tags without request values write nothing.
+    fn encode(&self, _value: &mut [u32]) {}
+
+    /// Decodes the response from `value`. Bytes the firmware did not write
+    /// are zero.
+    fn decode(value: &[u32]) -> Self::Response;
+}
+
+/// Gets the firmware revision.
+#[derive(Debug, Copy, Clone)]
+pub struct GetFirmwareRevision;
+
+impl Tag for GetFirmwareRevision {
+    type Response = u32;
+    const ID: u32 = 0x0000_0001;
+    const SIZE: usize = 4;
+
+    fn decode(value: &[u32]) -> u32 {
+        value[0]
+    }
+}
+
+/// Gets the board model.
+#[derive(Debug, Copy, Clone)]
+pub struct GetBoardModel;
+
+impl Tag for GetBoardModel {
+    type Response = u32;
+    const ID: u32 = 0x0001_0001;
+    const SIZE: usize = 4;
+
+    fn decode(value: &[u32]) -> u32 {
+        value[0]
+    }
+}
+
+/// Gets the board revision code.
+#[derive(Debug, Copy, Clone)]
+pub struct GetBoardRevision;
+
+impl Tag for GetBoardRevision {
+    type Response = u32;
+    const ID: u32 = 0x0001_0002;
+    const SIZE: usize = 4;
+
+    fn decode(value: &[u32]) -> u32 {
+        value[0]
+    }
+}
+
+/// Gets the MAC address of the board's network interface.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMacAddress;
+
+impl Tag for GetMacAddress {
+    type Response = [u8; 6];
+    const ID: u32 = 0x0001_0003;
+    const SIZE: usize = 6;
+
+    fn decode(value: &[u32]) -> [u8; 6] {
+        let (low, high) = (value[0], value[1]);
+        [
+            low as u8,
+            (low >> 8) as u8,
+            (low >> 16) as u8,
+            (low >> 24) as u8,
+            high as u8,
+            (high >> 8) as u8,
+        ]
+    }
+}
+
+/// Gets the board's serial number.
+#[derive(Debug, Copy, Clone)]
+pub struct GetBoardSerial;
+
+impl Tag for GetBoardSerial {
+    type Response = u64;
+    const ID: u32 = 0x0001_0004;
+    const SIZE: usize = 8;
+
+    fn decode(value: &[u32]) -> u64 {
+        (value[1] as u64) << 32 | value[0] as u64
+    }
+}
+
+/// Gets the `(base, size)` of the memory assigned to the ARM.
+#[derive(Debug, Copy, Clone)]
+pub struct GetArmMemory;
+
+impl Tag for GetArmMemory {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0001_0005;
+    const SIZE: usize = 8;
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// Gets the `(base, size)` of the memory assigned to the VideoCore.
+#[derive(Debug, Copy, Clone)]
+pub struct GetVcMemory;
+
+impl Tag for GetVcMemory {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0001_0006;
+    const SIZE: usize = 8;
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// A device whose power the firmware manages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerDevice {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+/// The power state of a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PowerState {
+    /// Whether the device is powered on.
+    pub on: bool,
+    /// Whether the device exists.
+    pub exists: bool,
+}
+
+impl PowerState {
+    fn decode(state: u32) -> PowerState {
+        PowerState {
+            on: state & 1 != 0,
+            exists: state & 2 == 0,
+        }
+    }
+}
+
+/// Gets the power state of a device.
+#[derive(Debug, Copy, Clone)]
+pub struct GetPowerState {
+    pub device: PowerDevice,
+}
+
+impl Tag for GetPowerState {
+    type Response = PowerState;
+    const ID: u32 = 0x0002_0001;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.device as u32;
+    }
+
+    fn decode(value: &[u32]) -> PowerState {
+        PowerState::decode(value[1])
+    }
+}
+
+/// Powers a device on or off. If `wait` is set, the firmware responds once
+/// the device is stable.
+#[derive(Debug, Copy, Clone)]
+pub struct SetPowerState {
+    pub device: PowerDevice,
+    pub on: bool,
+    pub wait: bool,
+}
+
+impl Tag for SetPowerState {
+    type Response = PowerState;
+    const ID: u32 = 0x0002_8001;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.device as u32;
+        value[1] = self.on as u32 | (self.wait as u32) << 1;
+    }
+
+    fn decode(value: &[u32]) -> PowerState {
+        PowerState::decode(value[1])
+    }
+}
+
+/// Gets the current rate of a clock in Hz. A rate of 0 means the clock
+/// doesn't exist.
+#[derive(Debug, Copy, Clone)]
+pub struct GetClockRate {
+    pub clock: Clock,
+}
+
+impl Tag for GetClockRate {
+    type Response = u32;
+    const ID: u32 = 0x0003_0002;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.clock as u32;
+    }
+
+    fn decode(value: &[u32]) -> u32 {
+        value[1]
+    }
+}
+
+/// Gets the maximum rate of a clock in Hz.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMaxClockRate {
+    pub clock: Clock,
+}
+
+impl Tag for GetMaxClockRate {
+    type Response = u32;
+    const ID: u32 = 0x0003_0004;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.clock as u32;
+    }
+
+    fn decode(value: &[u32]) -> u32 {
+        value[1]
+    }
+}
+
+/// Gets the minimum rate of a clock in Hz.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMinClockRate {
+    pub clock: Clock,
+}
+
+impl Tag for GetMinClockRate {
+    type Response = u32;
+    const ID: u32 = 0x0003_0007;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.clock as u32;
+    }
+
+    fn decode(value: &[u32]) -> u32 {
+        value[1]
+    }
+}
+
+/// Sets the rate of a clock in Hz. Responds with the rate actually set. If
+/// `skip_turbo` is set, setting the ARM clock doesn't raise voltages.
+#[derive(Debug, Copy, Clone)]
+pub struct SetClockRate {
+    pub clock: Clock,
+    pub rate: u32,
+    pub skip_turbo: bool,
+}
+
+impl Tag for SetClockRate {
+    type Response = u32;
+    const ID: u32 = 0x0003_8002;
+    const SIZE: usize = 12;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.clock as u32;
+        value[1] = self.rate;
+        value[2] = self.skip_turbo as u32;
+    }
+
+    fn decode(value: &[u32]) -> u32 {
+        value[1]
+    }
+}
+
+/// Gets the SoC's temperature in thousandths of a degree Celsius.
+#[derive(Debug, Copy, Clone)]
+pub struct GetTemperature;
+
+impl Tag for GetTemperature {
+    type Response = u32;
+    const ID: u32 = 0x0003_0006;
+    const SIZE: usize = 8;
+
+    fn decode(value: &[u32]) -> u32 {
+        value[1]
+    }
+}
+
+/// Gets the temperature in thousandths of a degree Celsius above which the
+/// firmware throttles the clocks.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMaxTemperature;
+
+impl Tag for GetMaxTemperature {
+    type Response = u32;
+    const ID: u32 = 0x0003_000A;
+    const SIZE: usize = 8;
+
+    fn decode(value: &[u32]) -> u32 {
+        value[1]
+    }
+}
+
+/// Allocates the framebuffer with the given alignment in bytes. Responds with
+/// the framebuffer's `(bus address, size)`; both are zero if no framebuffer
+/// could be allocated.
+#[derive(Debug, Copy, Clone)]
+pub struct AllocateBuffer {
+    pub alignment: u32,
+}
+
+impl Tag for AllocateBuffer {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0004_0001;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.alignment;
+    }
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// Releases the framebuffer.
+#[derive(Debug, Copy, Clone)]
+pub struct ReleaseBuffer;
+
+impl Tag for ReleaseBuffer {
+    type Response = ();
+    const ID: u32 = 0x0004_8001;
+    const SIZE: usize = 0;
+
+    fn decode(_value: &[u32]) {}
+}
+
+/// Gets the `(width, height)` of the display in pixels.
+#[derive(Debug, Copy, Clone)]
+pub struct GetPhysicalSize;
+
+impl Tag for GetPhysicalSize {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0004_0003;
+    const SIZE: usize = 8;
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// Sets the size of the display in pixels. Responds with the `(width,
+/// height)` actually set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetPhysicalSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tag for SetPhysicalSize {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0004_8003;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.width;
+        value[1] = self.height;
+    }
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// Sets the size of the framebuffer in pixels, which may be larger than the
+/// display. Responds with the `(width, height)` actually set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetVirtualSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tag for SetVirtualSize {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0004_8004;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.width;
+        value[1] = self.height;
+    }
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// Sets the offset in pixels of the displayed part of the framebuffer.
+/// Responds with the `(x, y)` offset actually set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetVirtualOffset {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Tag for SetVirtualOffset {
+    type Response = (u32, u32);
+    const ID: u32 = 0x0004_8009;
+    const SIZE: usize = 8;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.x;
+        value[1] = self.y;
+    }
+
+    fn decode(value: &[u32]) -> (u32, u32) {
+        (value[0], value[1])
+    }
+}
+
+/// Sets the number of bits per pixel. Responds with the depth actually set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetDepth {
+    pub bits: u32,
+}
+
+impl Tag for SetDepth {
+    type Response = u32;
+    const ID: u32 = 0x0004_8005;
+    const SIZE: usize = 4;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.bits;
+    }
+
+    fn decode(value: &[u32]) -> u32 {
+        value[0]
+    }
+}
+
+/// The order of the colour components of a pixel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelOrder {
+    Bgr = 0,
+    Rgb = 1,
+}
+
+/// Sets the order of the colour components of a pixel. Responds with the
+/// order actually set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetPixelOrder {
+    pub order: PixelOrder,
+}
+
+impl Tag for SetPixelOrder {
+    type Response = PixelOrder;
+    const ID: u32 = 0x0004_8006;
+    const SIZE: usize = 4;
+
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.order as u32;
+    }
+
+    fn decode(value: &[u32]) -> PixelOrder {
+        match value[0] {
+            0 => PixelOrder::Bgr,
+            _ => PixelOrder::Rgb,
+        }
+    }
+}
+
+/// Gets the number of bytes per line of the framebuffer.
+#[derive(Debug, Copy, Clone)]
+pub struct GetPitch;
+
+impl Tag for GetPitch {
+    type Response = u32;
+    const ID: u32 = 0x0004_0008;
+    const SIZE: usize = 4;
+
+    fn decode(value: &[u32]) -> u32 {
+        value[0]
+    }
+}
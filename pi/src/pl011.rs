@@ -0,0 +1,528 @@
+use core::fmt;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, WriteVolatile, Reserved};
+
+use timer;
+use common::IO_BASE;
+use gpio::{Gpio, Function};
+use mailbox::{self, Clock};
+use ring_buffer::RingBuffer;
+
+/// The base address for the PL011 UART registers.
+const UART0_REG_BASE: usize = IO_BASE + 0x201000;
+
+/// The default frequency of the UART reference clock set by the firmware
+/// (`init_uart_clock` in `config.txt`), used if the firmware can't be asked
+/// for the actual one.
+pub const UART_CLOCK_HZ: u32 = 48 * 1000 * 1000;
+
+/// Bit fields of the `FR` (flag) register.
+#[repr(u32)]
+enum Flag {
+    Busy = 1 << 3,
+    RxEmpty = 1 << 4,
+    TxFull = 1 << 5,
+}
+
+/// Bit fields of the `LCRH` (line control) register.
+#[repr(u32)]
+enum LineControl {
+    Break = 1 << 0,
+    ParityEnable = 1 << 1,
+    EvenParity = 1 << 2,
+    TwoStopBits = 1 << 3,
+    FifoEnable = 1 << 4,
+}
+
+/// Bit fields of the `CR` (control) register.
+#[repr(u32)]
+enum Control {
+    Enable = 1 << 0,
+    TxEnable = 1 << 8,
+    RxEnable = 1 << 9,
+}
+
+/// Bit fields shared by the `IMSC`, `RIS`, `MIS` and `ICR` interrupt
+/// registers.
+#[repr(u32)]
+enum Irq {
+    Rx = 1 << 4,
+    Tx = 1 << 5,
+    RxTimeout = 1 << 6,
+}
+
+/// All bits of the `ICR` register.
+const ICR_ALL: u32 = 0x7FF;
+
+/// The error bits of a word read from the `DR` register.
+const DR_FRAMING_ERROR: u32 = 1 << 8;
+const DR_PARITY_ERROR: u32 = 1 << 9;
+const DR_BREAK_ERROR: u32 = 1 << 10;
+const DR_OVERRUN_ERROR: u32 = 1 << 11;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    DR: Volatile<u32>,
+    RSRECR: Volatile<u32>,
+    __r0: [Reserved<u32>; 4],
+    FR: ReadVolatile<u32>,
+    __r1: [Reserved<u32>; 2],
+    IBRD: Volatile<u32>,
+    FBRD: Volatile<u32>,
+    LCRH: Volatile<u32>,
+    CR: Volatile<u32>,
+    IFLS: Volatile<u32>,
+    IMSC: Volatile<u32>,
+    RIS: ReadVolatile<u32>,
+    MIS: ReadVolatile<u32>,
+    ICR: WriteVolatile<u32>,
+    DMACR: Volatile<u32>,
+}
+
+/// The number of data bits in each character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataBits {
+    Five = 0b00,
+    Six = 0b01,
+    Seven = 0b10,
+    Eight = 0b11,
+}
+
+/// The parity bit sent with each character, if any.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// The number of stop bits sent after each character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// A FIFO fill level at which the receive or transmit interrupt is raised.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FifoLevel {
+    OneEighth = 0b000,
+    OneQuarter = 0b001,
+    OneHalf = 0b010,
+    ThreeQuarters = 0b011,
+    SevenEighths = 0b100,
+}
+
+/// Error type for UART configurations the hardware can't provide.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The baud rate can't be derived from the UART clock.
+    UnachievableBaudRate,
+}
+
+/// An error flagged on a received character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReceiveError {
+    /// The character had no valid stop bit.
+    Framing,
+    /// The character's parity bit didn't match the configured parity.
+    Parity,
+    /// The line was held low for longer than a character: a break.
+    Break,
+    /// The receive FIFO was full, so characters were lost.
+    Overrun,
+}
+
+/// Computes the integer and fractional baud rate divisors for `baud` given a
+/// UART clock of `clock` Hz.
+fn divisors(clock: u32, baud: u32) -> Result<(u32, u32), Error> {
+    if baud == 0 {
+        return Err(Error::UnachievableBaudRate);
+    }
+
+    // The divisor is `clock / (16 * baud)` with 6 fractional bits, rounded.
+    let divisor = (clock as u64 * 4 + baud as u64 / 2) / baud as u64;
+    let (integer, fraction) = ((divisor >> 6) as u32, (divisor & 0x3F) as u32);
+    match integer {
+        1...0xFFFE => Ok((integer, fraction)),
+        0xFFFF if fraction == 0 => Ok((integer, fraction)),
+        _ => Err(Error::UnachievableBaudRate),
+    }
+}
+
+/// A builder for a `Pl011` with a configuration other than the default of
+/// 115200 baud, 8 data bits, no parity and 1 stop bit (8N1), with the FIFOs
+/// enabled and interrupt levels at half full.
+#[derive(Debug, Copy, Clone)]
+pub struct Builder {
+    clock: u32,
+    baud: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+    fifos: bool,
+    rx_level: FifoLevel,
+    tx_level: FifoLevel,
+}
+
+impl Builder {
+    /// Sets the frequency of the UART reference clock in Hz. Defaults to the
+    /// rate the firmware reports, or `UART_CLOCK_HZ` if it can't be queried.
+    pub fn clock(mut self, hz: u32) -> Builder {
+        self.clock = hz;
+        self
+    }
+
+    /// Sets the baud rate.
+    pub fn baud(mut self, baud: u32) -> Builder {
+        self.baud = baud;
+        self
+    }
+
+    /// Sets the number of data bits per character.
+    pub fn data_bits(mut self, data_bits: DataBits) -> Builder {
+        self.data_bits = data_bits;
+        self
+    }
+
+    /// Sets the parity.
+    pub fn parity(mut self, parity: Parity) -> Builder {
+        self.parity = parity;
+        self
+    }
+
+    /// Sets the number of stop bits.
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Builder {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    /// Enables or disables the 16-entry transmit and receive FIFOs.
+    pub fn fifos(mut self, enabled: bool) -> Builder {
+        self.fifos = enabled;
+        self
+    }
+
+    /// Sets the FIFO levels at which the receive and transmit interrupts are
+    /// raised: when the receive FIFO fills to `rx` or the transmit FIFO
+    /// drains to `tx`.
+    pub fn fifo_levels(mut self, rx: FifoLevel, tx: FifoLevel) -> Builder {
+        self.rx_level = rx;
+        self.tx_level = tx;
+        self
+    }
+
+    /// Initializes the PL011 UART with this configuration and sets GPIO pins
+    /// 14 and 15 to alternative function 0 (TXD0/RXD0).
+    ///
+    /// On the Raspberry Pi 3, the PL011 drives the Bluetooth module unless it
+    /// is moved off with the `miniuart-bt` or `disable-bt` overlays.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableBaudRate` if the baud rate can't be
+    /// derived from the UART clock.
+    pub fn build(self) -> Result<Pl011, Error> {
+        let (integer, fraction) = divisors(self.clock, self.baud)?;
+
+        let registers = unsafe { &mut *(UART0_REG_BASE as *mut Registers) };
+
+        // Disable the UART and flush its FIFOs before reconfiguring it.
+        registers.CR.write(0);
+        while registers.FR.has_mask(Flag::Busy as u32) {}
+        registers.LCRH.write(0);
+
+        Gpio::new(14).into_alt(Function::Alt0);
+        Gpio::new(15).into_alt(Function::Alt0);
+
+        registers.ICR.write(ICR_ALL);
+        registers.IMSC.write(0);
+        registers.IBRD.write(integer);
+        registers.FBRD.write(fraction);
+        registers.IFLS.write((self.rx_level as u32) << 3 | self.tx_level as u32);
+
+        let mut line_control = (self.data_bits as u32) << 5;
+        match self.parity {
+            Parity::None => {}
+            Parity::Even => {
+                line_control |= LineControl::ParityEnable as u32 | LineControl::EvenParity as u32
+            }
+            Parity::Odd => line_control |= LineControl::ParityEnable as u32,
+        }
+        if self.stop_bits == StopBits::Two {
+            line_control |= LineControl::TwoStopBits as u32;
+        }
+        if self.fifos {
+            line_control |= LineControl::FifoEnable as u32;
+        }
+        registers.LCRH.write(line_control);
+
+        registers.CR.write(
+            Control::Enable as u32 | Control::TxEnable as u32 | Control::RxEnable as u32,
+        );
+
+        Ok(Pl011 {
+            registers,
+            clock: self.clock,
+            timeout: None,
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            interrupts: false,
+        })
+    }
+}
+
+/// The Raspberry Pi's PL011 UART, UART0.
+///
+/// Like `MiniUart`, received bytes are buffered in software by `poll()` and,
+/// once interrupts are enabled with `enable_interrupts()`, so are bytes that
+/// are written.
+pub struct Pl011 {
+    registers: &'static mut Registers,
+    clock: u32,
+    timeout: Option<u32>,
+    rx: RingBuffer,
+    tx: RingBuffer,
+    interrupts: bool,
+}
+
+impl Pl011 {
+    /// Returns a builder for a `Pl011` with the default configuration.
+    pub fn builder() -> Builder {
+        Builder {
+            clock: mailbox::clock_rate(Clock::Uart).unwrap_or(UART_CLOCK_HZ),
+            baud: 115200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            fifos: true,
+            rx_level: FifoLevel::OneHalf,
+            tx_level: FifoLevel::OneHalf,
+        }
+    }
+
+    /// Initializes the PL011 UART with the default configuration: 115200
+    /// baud, 8N1 and the FIFOs enabled. See `Builder::build()`.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    pub fn new() -> Pl011 {
+        Pl011::builder().build().expect("default PL011 configuration")
+    }
+
+    /// Sets the baud rate to `baud`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableBaudRate` if the baud rate can't be
+    /// derived from the UART clock. The baud rate is unchanged.
+    pub fn set_baud_rate(&mut self, baud: u32) -> Result<(), Error> {
+        let (integer, fraction) = divisors(self.clock, baud)?;
+        self.flush();
+        while self.registers.FR.has_mask(Flag::Busy as u32) {}
+
+        self.registers.IBRD.write(integer);
+        self.registers.FBRD.write(fraction);
+        // The divisors only take effect on a write to `LCRH`.
+        let line_control = self.registers.LCRH.read();
+        self.registers.LCRH.write(line_control);
+        Ok(())
+    }
+
+    /// Set the read timeout to `milliseconds` milliseconds.
+    pub fn set_read_timeout(&mut self, milliseconds: u32) {
+        self.timeout = Some(milliseconds);
+    }
+
+    /// Starts or stops sending a break: holding the transmit line low.
+    pub fn set_break(&mut self, on: bool) {
+        match on {
+            true => self.registers.LCRH.or_mask(LineControl::Break as u32),
+            false => self.registers.LCRH.and_mask(!(LineControl::Break as u32)),
+        }
+    }
+
+    /// Enables the receive, receive timeout and transmit interrupts. `poll()`
+    /// must then be called whenever `Interrupt::Uart` is pending. Enabling
+    /// `Interrupt::Uart` in the interrupt controller is up to the caller.
+    pub fn enable_interrupts(&mut self) {
+        self.interrupts = true;
+        self.registers.IMSC.write(Irq::Rx as u32 | Irq::RxTimeout as u32);
+    }
+
+    /// Moves bytes from the receive FIFO into the receive buffer and from the
+    /// transmit buffer into the transmit FIFO, and clears the UART's
+    /// interrupts. This is the UART's interrupt handler, but it may be
+    /// called at any time. Never blocks.
+    ///
+    /// Received bytes are dropped if the receive buffer is full. Receive
+    /// errors are dropped too; use `read_byte_checked()` to see them.
+    pub fn poll(&mut self) {
+        self.registers.ICR.write(Irq::Rx as u32 | Irq::RxTimeout as u32 | Irq::Tx as u32);
+
+        while !self.registers.FR.has_mask(Flag::RxEmpty as u32) {
+            let byte = self.registers.DR.read() as u8;
+            self.rx.push(byte);
+        }
+
+        while !self.tx.is_empty() && !self.registers.FR.has_mask(Flag::TxFull as u32) {
+            let byte = self.tx.pop().unwrap();
+            self.registers.DR.write(byte as u32);
+        }
+
+        if self.tx.is_empty() {
+            self.registers.IMSC.and_mask(!(Irq::Tx as u32));
+        }
+    }
+
+    /// Blocks until every buffered byte has been moved to the transmit FIFO.
+    pub fn flush(&mut self) {
+        while !self.tx.is_empty() {
+            self.poll();
+        }
+    }
+
+    /// Write the byte `byte`. If interrupts are enabled, the byte is buffered
+    /// and this method only blocks while the transmit buffer is full.
+    /// Otherwise, this method blocks until there is space available in the
+    /// transmit FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        let fifo_full = self.registers.FR.has_mask(Flag::TxFull as u32);
+        if !self.interrupts || (self.tx.is_empty() && !fifo_full) {
+            // The transmit interrupt only fires when the FIFO drains past
+            // its level, so the FIFO is filled directly whenever possible.
+            while self.registers.FR.has_mask(Flag::TxFull as u32) {}
+            self.registers.DR.write(byte as u32);
+            return;
+        }
+
+        while !self.tx.push(byte) {
+            self.poll();
+        }
+        self.registers.IMSC.or_mask(Irq::Tx as u32);
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. If this
+    /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn has_byte(&self) -> bool {
+        !self.rx.is_empty() || !self.registers.FR.has_mask(Flag::RxEmpty as u32)
+    }
+
+    /// Blocks until there is a byte ready to read. If a read timeout is set,
+    /// this method blocks for at most that amount of time. Otherwise, this
+    /// method blocks indefinitely until there is a byte to read.
+    ///
+    /// Returns `Ok(())` if a byte is ready to read. Returns `Err(())` if the
+    /// timeout expired while waiting for a byte to be ready.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        match self.timeout {
+            Some(ms) => {
+                let end_time = ms as u64 * 1000 + timer::current_time();
+                while !self.has_byte() {
+                    if timer::current_time() >= end_time {
+                        return Err(());
+                    }
+                }
+                Ok(())
+            }
+            None => {
+                while !self.has_byte() {}
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads a byte if one is ready. Never blocks.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        self.poll();
+        self.rx.pop()
+    }
+
+    /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
+    pub fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.try_read_byte() {
+                return byte;
+            }
+        }
+    }
+
+    /// Reads a byte directly from the receive FIFO, reporting any error
+    /// flagged on it. Blocks indefinitely until a byte is received. Bytes
+    /// already buffered by `poll()` are returned first, without errors.
+    pub fn read_byte_checked(&mut self) -> Result<u8, ReceiveError> {
+        if let Some(byte) = self.rx.pop() {
+            return Ok(byte);
+        }
+
+        while self.registers.FR.has_mask(Flag::RxEmpty as u32) {}
+        let data = self.registers.DR.read();
+        if data & DR_BREAK_ERROR != 0 {
+            Err(ReceiveError::Break)
+        } else if data & DR_FRAMING_ERROR != 0 {
+            Err(ReceiveError::Framing)
+        } else if data & DR_PARITY_ERROR != 0 {
+            Err(ReceiveError::Parity)
+        } else if data & DR_OVERRUN_ERROR != 0 {
+            Err(ReceiveError::Overrun)
+        } else {
+            Ok(data as u8)
+        }
+    }
+}
+
+impl fmt::Write for Pl011 {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+mod uart_io {
+    use std::io;
+    use super::Pl011;
+
+    // Like `MiniUart`'s, reads wait at most the read timeout for the first
+    // byte and then read as many bytes as are ready.
+    impl io::Read for Pl011 {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.wait_for_byte() {
+                Ok(()) => {
+                    let mut count = 0;
+
+                    while self.has_byte() && count < buf.len() {
+                        buf[count] = self.read_byte();
+                        count += 1;
+                    }
+
+                    Ok(count)
+                }
+                Err(()) => Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out"))
+            }
+        }
+    }
+
+    impl io::Write for Pl011 {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &byte in buf {
+                self.write_byte(byte);
+            }
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Pl011::flush(self);
+            Ok(())
+        }
+    }
+}
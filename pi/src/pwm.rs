@@ -0,0 +1,361 @@
+use volatile::prelude::*;
+use volatile::{Volatile, Reserved};
+
+use common::IO_BASE;
+use gpio::{Gpio, Function};
+
+/// The base address for the PWM registers.
+const PWM_REG_BASE: usize = IO_BASE + 0x20C000;
+
+/// The base address for the PWM clock's clock manager registers.
+const PWM_CLOCK_BASE: usize = IO_BASE + 0x1010A0;
+
+/// The address of the `FIF1` register, for DMA transfers to the FIFO.
+pub const FIFO_ADDRESS: usize = PWM_REG_BASE + 0x18;
+
+/// The frequency of the crystal oscillator clock source in Hz.
+pub const OSCILLATOR_HZ: u32 = 19_200_000;
+
+/// The frequency of the PLLD clock source in Hz.
+pub const PLLD_HZ: u32 = 500_000_000;
+
+/// The password clock manager registers only accept writes with, in the
+/// bits of `CM_PASSWORD_MASK`.
+const CM_PASSWORD: u32 = 0x5A << 24;
+const CM_PASSWORD_MASK: u32 = 0xFF << 24;
+
+/// Bit fields of the clock manager `CTL` register.
+#[repr(u32)]
+enum ClockControl {
+    Enable = 1 << 4,
+    Busy = 1 << 7,
+}
+
+/// The offset of the `MASH` field in the clock manager `CTL` register. A
+/// value of 1 enables the fractional divider.
+const CM_MASH_SHIFT: u32 = 9;
+
+/// The largest integer part of a clock divider.
+const CM_DIVI_MAX: u32 = 0xFFF;
+
+/// Bit fields of the `CTL` register for channel 1. Channel 2's are shifted
+/// left by `CHANNEL_2_SHIFT`.
+#[repr(u32)]
+enum Control {
+    Enable = 1 << 0,
+    Serializer = 1 << 1,
+    RepeatLast = 1 << 2,
+    SilenceHigh = 1 << 3,
+    InvertPolarity = 1 << 4,
+    UseFifo = 1 << 5,
+    MarkSpace = 1 << 7,
+}
+
+/// The `CLRF1` bit of the `CTL` register, which clears the FIFO.
+const CTL_CLEAR_FIFO: u32 = 1 << 6;
+
+/// The shift from a channel 1 bit to the channel 2 bit in `CTL`.
+const CHANNEL_2_SHIFT: u32 = 8;
+
+/// Bit fields of the `STA` register.
+#[repr(u32)]
+enum Status {
+    FifoFull = 1 << 0,
+    FifoEmpty = 1 << 1,
+    FifoWriteError = 1 << 2,
+    FifoReadError = 1 << 3,
+    Channel1Gap = 1 << 4,
+    Channel2Gap = 1 << 5,
+    BusError = 1 << 8,
+}
+
+/// The `ENAB` bit of the `DMAC` register.
+const DMAC_ENABLE: u32 = 1 << 31;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTL: Volatile<u32>,
+    STA: Volatile<u32>,
+    DMAC: Volatile<u32>,
+    __r0: Reserved<u32>,
+    RNG1: Volatile<u32>,
+    DAT1: Volatile<u32>,
+    FIF1: Volatile<u32>,
+    __r1: Reserved<u32>,
+    RNG2: Volatile<u32>,
+    DAT2: Volatile<u32>,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct ClockRegisters {
+    CTL: Volatile<u32>,
+    DIV: Volatile<u32>,
+}
+
+/// One of the two PWM channels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    One,
+    Two,
+}
+
+impl Channel {
+    /// Returns the `CTL` bit `control` for this channel.
+    fn control(self, control: Control) -> u32 {
+        match self {
+            Channel::One => control as u32,
+            Channel::Two => (control as u32) << CHANNEL_2_SHIFT,
+        }
+    }
+}
+
+/// How a channel turns its data and range into an output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// The output is high for `data` out of every `range` clock cycles,
+    /// spread out as evenly as possible. This gives the highest frequency
+    /// for a duty cycle, which suits filtered outputs such as audio.
+    Balanced,
+    /// The output is high for the first `data` clock cycles of every `range`
+    /// cycles. This gives a fixed period, as servos need.
+    MarkSpace,
+    /// The output is the `range` most significant bits of each data word,
+    /// sent one bit per clock cycle.
+    Serializer,
+}
+
+/// Error type for PWM configurations the hardware can't provide.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The clock frequency can't be derived from a clock source.
+    UnachievableClock,
+    /// The pin can't be routed to a PWM channel.
+    InvalidPin(u8),
+}
+
+/// Returns the channel and alternative function that route `pin` to PWM.
+fn pin_function(pin: u8) -> Option<(Channel, Function)> {
+    match pin {
+        12 | 40 => Some((Channel::One, Function::Alt0)),
+        13 | 41 | 45 => Some((Channel::Two, Function::Alt0)),
+        18 => Some((Channel::One, Function::Alt5)),
+        19 => Some((Channel::Two, Function::Alt5)),
+        _ => None,
+    }
+}
+
+/// Returns the clock source, integer and fractional divider that derive
+/// `hz` from one of the clock sources.
+fn divider(hz: u32) -> Result<(u32, u32, u32), Error> {
+    if hz == 0 {
+        return Err(Error::UnachievableClock);
+    }
+
+    // Source 1 is the oscillator and source 6 PLLD, which is only used for
+    // clocks the oscillator can't reach.
+    for &(source, source_hz) in [(1, OSCILLATOR_HZ), (6, PLLD_HZ)].iter() {
+        let integer = source_hz / hz;
+        let fraction = ((source_hz % hz) as u64 * 4096 / hz as u64) as u32;
+        if integer >= 2 && integer <= CM_DIVI_MAX {
+            return Ok((source, integer, fraction));
+        }
+    }
+
+    Err(Error::UnachievableClock)
+}
+
+/// The PWM controller and its two channels.
+///
+/// Each channel's output is defined by a range and data register, whose
+/// meaning depends on its `Mode`, and is clocked by the PWM clock shared by
+/// both channels. For instance, a servo expecting a 1.5 ms pulse every 20 ms
+/// on GPIO 18 can be driven with:
+///
+/// ```rust,ignore
+/// let mut pwm = Pwm::new(1_000_000)?;
+/// let channel = pwm.route(18)?;
+/// pwm.set_mode(channel, Mode::MarkSpace);
+/// pwm.set_range(channel, 20_000);
+/// pwm.set_data(channel, 1_500);
+/// pwm.enable(channel);
+/// ```
+pub struct Pwm {
+    registers: &'static mut Registers,
+    clock: &'static mut ClockRegisters,
+}
+
+impl Pwm {
+    /// Returns the PWM controller with both channels disabled and its clock
+    /// running at `clock_hz`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableClock` if `clock_hz` can't be derived from
+    /// one of the clock sources.
+    pub fn new(clock_hz: u32) -> Result<Pwm, Error> {
+        let mut pwm = Pwm {
+            registers: unsafe { &mut *(PWM_REG_BASE as *mut Registers) },
+            clock: unsafe { &mut *(PWM_CLOCK_BASE as *mut ClockRegisters) },
+        };
+
+        pwm.registers.CTL.write(CTL_CLEAR_FIFO);
+        pwm.set_clock(clock_hz)?;
+        pwm.clear_errors();
+        Ok(pwm)
+    }
+
+    /// Sets the PWM clock to `hz`. The clock is stopped while it changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableClock` if `hz` can't be derived from one
+    /// of the clock sources. The clock is then unchanged.
+    pub fn set_clock(&mut self, hz: u32) -> Result<(), Error> {
+        let (source, integer, fraction) = divider(hz)?;
+
+        // The divider may only change while the clock is stopped.
+        let ctl = self.clock.CTL.read() & !(CM_PASSWORD_MASK | ClockControl::Enable as u32);
+        self.clock.CTL.write(CM_PASSWORD | ctl);
+        while self.clock.CTL.has_mask(ClockControl::Busy as u32) {}
+
+        // The source is set before enabling the clock, as changing both at
+        // once can glitch.
+        let mash = if fraction != 0 { 1 } else { 0 };
+        let ctl = CM_PASSWORD | mash << CM_MASH_SHIFT | source;
+        self.clock.DIV.write(CM_PASSWORD | integer << 12 | fraction);
+        self.clock.CTL.write(ctl);
+        self.clock.CTL.write(ctl | ClockControl::Enable as u32);
+
+        Ok(())
+    }
+
+    /// Switches GPIO `pin` to its PWM function. Returns the channel whose
+    /// output it carries.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPin` if `pin` can't carry a PWM output. PWM is
+    /// available on GPIO 12, 18 and 40 (channel 1) and 13, 19, 41 and 45
+    /// (channel 2). GPIO 40 and 45 drive the audio jack on most boards.
+    pub fn route(&mut self, pin: u8) -> Result<Channel, Error> {
+        let (channel, function) = pin_function(pin).ok_or(Error::InvalidPin(pin))?;
+        Gpio::new(pin).into_alt(function);
+        Ok(channel)
+    }
+
+    /// Sets the `CTL` bit `control` of `channel` if `on`, or clears it.
+    fn set_control(&mut self, channel: Channel, control: Control, on: bool) {
+        let bit = channel.control(control);
+        if on {
+            self.registers.CTL.or_mask(bit);
+        } else {
+            self.registers.CTL.and_mask(!(bit | CTL_CLEAR_FIFO));
+        }
+    }
+
+    /// Sets the mode of `channel`.
+    pub fn set_mode(&mut self, channel: Channel, mode: Mode) {
+        self.set_control(channel, Control::MarkSpace, mode == Mode::MarkSpace);
+        self.set_control(channel, Control::Serializer, mode == Mode::Serializer);
+    }
+
+    /// Sets the range of `channel`: the period in clock cycles in the
+    /// `Balanced` and `MarkSpace` modes, or the number of bits sent from each
+    /// word in the `Serializer` mode.
+    pub fn set_range(&mut self, channel: Channel, range: u32) {
+        match channel {
+            Channel::One => self.registers.RNG1.write(range),
+            Channel::Two => self.registers.RNG2.write(range),
+        }
+    }
+
+    /// Sets the data of `channel`: the number of clock cycles per period the
+    /// output is high in the `Balanced` and `MarkSpace` modes, or the word
+    /// sent in the `Serializer` mode. Unused if the channel reads the FIFO.
+    pub fn set_data(&mut self, channel: Channel, data: u32) {
+        match channel {
+            Channel::One => self.registers.DAT1.write(data),
+            Channel::Two => self.registers.DAT2.write(data),
+        }
+    }
+
+    /// Inverts the output of `channel` if `inverted` is `true`.
+    pub fn set_inverted(&mut self, channel: Channel, inverted: bool) {
+        self.set_control(channel, Control::InvertPolarity, inverted);
+    }
+
+    /// Sets the level `channel` outputs while disabled or out of data.
+    pub fn set_silence_high(&mut self, channel: Channel, high: bool) {
+        self.set_control(channel, Control::SilenceHigh, high);
+    }
+
+    /// Makes `channel` read its data from the FIFO rather than its data
+    /// register if `fifo` is `true`. If both channels read the FIFO, its
+    /// words alternate between them. If `repeat` is `true`, the last word is
+    /// repeated when the FIFO runs empty; otherwise the channel goes silent.
+    pub fn set_fifo(&mut self, channel: Channel, fifo: bool, repeat: bool) {
+        self.set_control(channel, Control::UseFifo, fifo);
+        self.set_control(channel, Control::RepeatLast, repeat);
+    }
+
+    /// Starts the output of `channel`.
+    pub fn enable(&mut self, channel: Channel) {
+        self.set_control(channel, Control::Enable, true);
+    }
+
+    /// Stops the output of `channel`.
+    pub fn disable(&mut self, channel: Channel) {
+        self.set_control(channel, Control::Enable, false);
+    }
+
+    /// Discards all words in the FIFO.
+    pub fn clear_fifo(&mut self) {
+        self.registers.CTL.or_mask(CTL_CLEAR_FIFO);
+    }
+
+    /// Returns `true` if the FIFO can't take another word.
+    pub fn is_fifo_full(&self) -> bool {
+        self.registers.STA.has_mask(Status::FifoFull as u32)
+    }
+
+    /// Returns `true` if the FIFO holds no words.
+    pub fn is_fifo_empty(&self) -> bool {
+        self.registers.STA.has_mask(Status::FifoEmpty as u32)
+    }
+
+    /// Appends `word` to the FIFO, blocking until there is space for it.
+    pub fn write_fifo(&mut self, word: u32) {
+        while self.is_fifo_full() {}
+        self.registers.FIF1.write(word);
+    }
+
+    /// Makes the PWM request DMA transfers to the FIFO at `FIFO_ADDRESS`,
+    /// paced by `dma::Dreq::Pwm`, while it holds fewer than `threshold`
+    /// words. The request is raised as a panic, to take priority, below
+    /// `panic` words.
+    pub fn enable_dma(&mut self, threshold: u8, panic: u8) {
+        self.registers.DMAC.write(DMAC_ENABLE | (panic as u32) << 8 | threshold as u32);
+    }
+
+    /// Stops DMA requests.
+    pub fn disable_dma(&mut self) {
+        self.registers.DMAC.and_mask(!DMAC_ENABLE);
+    }
+
+    /// Clears the FIFO, gap and bus error flags. Returns `true` if any were
+    /// set: the FIFO was written while full or read while empty, a channel
+    /// ran out of data, or a register write collided with the PWM.
+    pub fn clear_errors(&mut self) -> bool {
+        let errors = Status::FifoWriteError as u32
+            | Status::FifoReadError as u32
+            | Status::Channel1Gap as u32
+            | Status::Channel2Gap as u32
+            | Status::BusError as u32;
+
+        let flagged = self.registers.STA.read() & errors;
+        self.registers.STA.write(flagged);
+        flagged != 0
+    }
+}
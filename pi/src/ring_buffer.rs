@@ -0,0 +1,61 @@
+/// The capacity of a `RingBuffer` in bytes. Must be a power of two.
+const CAPACITY: usize = 256;
+
+/// A fixed-capacity FIFO queue of bytes, used to buffer data between a
+/// device's interrupt handler and its readers and writers.
+pub struct RingBuffer {
+    data: [u8; CAPACITY],
+    /// The index of the next byte to pop, modulo `CAPACITY`.
+    head: usize,
+    /// The index of the next byte to push, modulo `CAPACITY`.
+    tail: usize,
+}
+
+impl RingBuffer {
+    /// Returns a new, empty ring buffer.
+    pub const fn new() -> RingBuffer {
+        RingBuffer {
+            data: [0; CAPACITY],
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// Returns the number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        self.tail.wrapping_sub(self.head)
+    }
+
+    /// Returns `true` if the buffer holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be pushed.
+    pub fn is_full(&self) -> bool {
+        self.len() == CAPACITY
+    }
+
+    /// Appends `byte` to the buffer. Returns `false`, leaving the buffer
+    /// unmodified, if it is full.
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+
+        self.data[self.tail % CAPACITY] = byte;
+        self.tail = self.tail.wrapping_add(1);
+        true
+    }
+
+    /// Removes and returns the oldest byte in the buffer, if any.
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let byte = self.data[self.head % CAPACITY];
+        self.head = self.head.wrapping_add(1);
+        Some(byte)
+    }
+}
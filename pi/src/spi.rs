@@ -0,0 +1,215 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use common::{divider, IO_BASE, CORE_CLOCK_HZ};
+use gpio::{Gpio, Function};
+use mailbox::{self, Clock};
+
+/// The base address for the SPI0 registers.
+const SPI0_REG_BASE: usize = IO_BASE + 0x204000;
+
+/// Bit fields of the `CS` (control and status) register.
+#[repr(u32)]
+enum Control {
+    ClockPhase = 1 << 2,
+    ClockPolarity = 1 << 3,
+    ClearTx = 1 << 4,
+    ClearRx = 1 << 5,
+    TransferActive = 1 << 7,
+    Done = 1 << 16,
+    RxData = 1 << 17,
+    TxSpace = 1 << 18,
+}
+
+/// The `CS` field of the `CS` register, selecting the chip select line.
+const CS_SELECT_MASK: u32 = 0b11;
+
+/// The bit of the `CS` register holding the polarity of chip select 0.
+/// Chip select 1's is the next bit.
+const CS_POLARITY_SHIFT: u32 = 21;
+
+/// The largest clock divider. A `CLK` value of 0 selects it.
+const MAX_DIVIDER: u32 = 65536;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CS: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    CLK: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    LTOH: Volatile<u32>,
+    DC: Volatile<u32>,
+}
+
+/// The clock polarity and phase, numbered as SPI modes usually are.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// The clock idles low and data is sampled on the rising edge.
+    Mode0,
+    /// The clock idles low and data is sampled on the falling edge.
+    Mode1,
+    /// The clock idles high and data is sampled on the falling edge.
+    Mode2,
+    /// The clock idles high and data is sampled on the rising edge.
+    Mode3,
+}
+
+/// A chip select line, which selects the device a transfer talks to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChipSelect {
+    /// `CE0`, on GPIO 8.
+    Ce0 = 0,
+    /// `CE1`, on GPIO 7.
+    Ce1 = 1,
+}
+
+/// Error type for SPI configurations the hardware can't provide.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The clock frequency can't be derived from the core clock.
+    UnachievableClock,
+}
+
+/// The SPI0 master, with polled, full-duplex transfers.
+///
+/// ```rust,ignore
+/// let mut spi = Spi::new();
+/// spi.set_clock(1_000_000)?;
+/// spi.set_mode(Mode::Mode0);
+/// spi.select(ChipSelect::Ce0);
+/// let mut buf = [0x01, 0x80, 0x00];
+/// spi.transfer(&mut buf);
+/// ```
+pub struct Spi {
+    registers: &'static mut Registers,
+    /// The frequency of the core clock in Hz.
+    clock: u32,
+}
+
+impl Spi {
+    /// Initializes SPI0 by setting GPIO pins 7 through 11 to alternative
+    /// function 0 (CE1, CE0, MISO, MOSI and SCLK). The SPI clock starts at
+    /// the slowest rate, in mode 0, with `CE0` selected and active low.
+    pub fn new() -> Spi {
+        for pin in 7..12 {
+            Gpio::new(pin).into_alt(Function::Alt0);
+        }
+
+        let registers = unsafe { &mut *(SPI0_REG_BASE as *mut Registers) };
+        registers.CS.write(Control::ClearTx as u32 | Control::ClearRx as u32);
+        registers.CLK.write(0);
+
+        Spi {
+            registers,
+            clock: mailbox::clock_rate(Clock::Core).unwrap_or(CORE_CLOCK_HZ),
+        }
+    }
+
+    /// Sets the SPI clock to the fastest rate no faster than `hz` that can be
+    /// derived from the core clock. Returns that rate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableClock` if `hz` is 0 or below the slowest
+    /// rate. The clock is then unchanged.
+    pub fn set_clock(&mut self, hz: u32) -> Result<u32, Error> {
+        let divider = divider(self.clock, hz, MAX_DIVIDER).ok_or(Error::UnachievableClock)?;
+        self.registers.CLK.write(divider % MAX_DIVIDER);
+        Ok(self.clock / divider)
+    }
+
+    /// Sets the clock polarity and phase.
+    pub fn set_mode(&mut self, mode: Mode) {
+        let (polarity, phase) = match mode {
+            Mode::Mode0 => (false, false),
+            Mode::Mode1 => (false, true),
+            Mode::Mode2 => (true, false),
+            Mode::Mode3 => (true, true),
+        };
+
+        let bits = Control::ClockPolarity as u32 | Control::ClockPhase as u32;
+        let mut cs = self.registers.CS.read() & !bits;
+        if polarity {
+            cs |= Control::ClockPolarity as u32;
+        }
+        if phase {
+            cs |= Control::ClockPhase as u32;
+        }
+        self.registers.CS.write(cs);
+    }
+
+    /// Selects the device on `chip_select` for the following transfers.
+    pub fn select(&mut self, chip_select: ChipSelect) {
+        let cs = self.registers.CS.read() & !CS_SELECT_MASK;
+        self.registers.CS.write(cs | chip_select as u32);
+    }
+
+    /// Makes `chip_select` active high if `high` is `true`, or active low,
+    /// as most devices expect.
+    pub fn set_active_high(&mut self, chip_select: ChipSelect, high: bool) {
+        let bit = 1 << (CS_POLARITY_SHIFT + chip_select as u32);
+        if high {
+            self.registers.CS.or_mask(bit);
+        } else {
+            self.registers.CS.and_mask(!bit);
+        }
+    }
+
+    /// Sends the bytes in `buf` to the selected device while replacing them
+    /// with the bytes received from it. The chip select line is held active
+    /// for the whole transfer. Blocks until the transfer is complete.
+    pub fn transfer(&mut self, buf: &mut [u8]) {
+        self.begin();
+
+        // A byte is received for every byte sent. Bytes are only read back
+        // after being sent, so `buf` can be reused for both.
+        let (mut sent, mut received) = (0, 0);
+        while received < buf.len() {
+            while sent < buf.len() && self.registers.CS.has_mask(Control::TxSpace as u32) {
+                self.registers.FIFO.write(buf[sent] as u32);
+                sent += 1;
+            }
+
+            while received < sent && self.registers.CS.has_mask(Control::RxData as u32) {
+                buf[received] = self.registers.FIFO.read() as u8;
+                received += 1;
+            }
+        }
+
+        self.end();
+    }
+
+    /// Sends `data` to the selected device, discarding the bytes received.
+    pub fn write(&mut self, data: &[u8]) {
+        self.begin();
+
+        let (mut sent, mut received) = (0, 0);
+        while received < data.len() {
+            while sent < data.len() && self.registers.CS.has_mask(Control::TxSpace as u32) {
+                self.registers.FIFO.write(data[sent] as u32);
+                sent += 1;
+            }
+
+            while received < sent && self.registers.CS.has_mask(Control::RxData as u32) {
+                self.registers.FIFO.read();
+                received += 1;
+            }
+        }
+
+        self.end();
+    }
+
+    /// Clears the FIFOs and starts a transfer, activating the chip select
+    /// line.
+    fn begin(&mut self) {
+        let clear = Control::ClearTx as u32 | Control::ClearRx as u32;
+        self.registers.CS.or_mask(clear | Control::TransferActive as u32);
+    }
+
+    /// Waits for the last byte to be sent and ends the transfer.
+    fn end(&mut self) {
+        while !self.registers.CS.has_mask(Control::Done as u32) {}
+        self.registers.CS.and_mask(!(Control::TransferActive as u32));
+    }
+}
@@ -1,4 +1,5 @@
 use common::IO_BASE;
+use interrupt::Interrupt;
 use volatile::prelude::*;
 use volatile::{Volatile, ReadVolatile};
 
@@ -14,6 +15,32 @@ struct Registers {
     COMPARE: [Volatile<u32>; 4]
 }
 
+/// One of the system timer's four compare channels. A channel matches, and
+/// raises its interrupt, when the low 32 bits of the counter equal its
+/// compare value.
+///
+/// The GPU firmware uses channels 0 and 2, which leaves channels 1 and 3 to
+/// the ARM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Zero = 0,
+    One = 1,
+    Two = 2,
+    Three = 3,
+}
+
+impl Channel {
+    /// Returns the interrupt raised when this channel matches.
+    pub fn interrupt(self) -> Interrupt {
+        match self {
+            Channel::Zero => Interrupt::Timer0,
+            Channel::One => Interrupt::Timer1,
+            Channel::Two => Interrupt::Timer2,
+            Channel::Three => Interrupt::Timer3,
+        }
+    }
+}
+
 /// The Raspberry Pi ARM system timer.
 pub struct Timer {
     registers: &'static mut Registers
@@ -39,9 +66,33 @@ impl Timer {
     /// interrupts for timer 1 are enabled and IRQs are unmasked, then a timer
     /// interrupt will be issued in `us` microseconds.
     pub fn tick_in(&mut self, us: u32) {
-        let time = self.registers.CLO.read() + us;
-        self.registers.COMPARE[1].write(time);
-        self.registers.CS.write(0b0010);
+        self.compare_in(Channel::One, us);
+    }
+
+    /// Sets up a match on `channel` to occur `us` microseconds from now,
+    /// clearing any earlier match.
+    pub fn compare_in(&mut self, channel: Channel, us: u32) {
+        let time = self.registers.CLO.read().wrapping_add(us);
+        self.set_compare(channel, time);
+    }
+
+    /// Sets up a match on `channel` to occur when the low 32 bits of the
+    /// counter reach `time`, clearing any earlier match. A `time` that has
+    /// just passed only matches once the counter wraps around, about 71
+    /// minutes later.
+    pub fn set_compare(&mut self, channel: Channel, time: u32) {
+        self.registers.COMPARE[channel as usize].write(time);
+        self.clear_match(channel);
+    }
+
+    /// Returns `true` if `channel` matched since its match was last cleared.
+    pub fn has_matched(&self, channel: Channel) -> bool {
+        self.registers.CS.has_mask(1 << channel as u32)
+    }
+
+    /// Clears the match on `channel`, and with it the channel's interrupt.
+    pub fn clear_match(&mut self, channel: Channel) {
+        self.registers.CS.write(1 << channel as u32);
     }
 }
 
@@ -4,12 +4,18 @@ use volatile::prelude::*;
 use volatile::{Volatile, ReadVolatile, Reserved};
 
 use timer;
-use common::IO_BASE;
+use common::{IO_BASE, CORE_CLOCK_HZ};
 use gpio::{Gpio, Function};
+use mailbox::{self, Clock};
+use ring_buffer::RingBuffer;
 
 /// The base address for the `MU` registers.
 const MU_REG_BASE: usize = IO_BASE + 0x215040;
 
+/// The largest deviation from a requested baud rate that is accepted, in
+/// percent.
+const MAX_BAUD_ERROR_PERCENT: u64 = 2;
+
 /// The `AUXENB` register from page 9 of the BCM2837 documentation.
 const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>;
 
@@ -20,6 +26,13 @@ enum LsrStatus {
     TxAvailable = 1 << 5,
 }
 
+/// Bit fields of the `AUX_MU_IER_REG` register. The BCM2837 documentation
+/// has the receive and transmit bits swapped and omits that bits 2 and 3 must
+/// be set for interrupts to be raised at all.
+const IER_RX: u8 = 1 << 0;
+const IER_TX: u8 = 1 << 1;
+const IER_REQUIRED: u8 = 0b11 << 2;
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -45,21 +58,92 @@ struct Registers {
     AUX_MU_BAUD_REG: Volatile<u16>,
 }
 
-/// The Raspberry Pi's "mini UART".
-pub struct MiniUart {
-    registers: &'static mut Registers,
-    timeout: Option<u32>,
+/// The number of data bits in each character. The mini UART supports no
+/// other sizes, nor parity or more than one stop bit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataBits {
+    Seven = 0b00,
+    // The documentation says 0b01, but bit 1 must be set too.
+    Eight = 0b11,
 }
 
-impl MiniUart {
+/// Error type for UART configurations the hardware can't provide.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The baud rate can't be derived from the core clock closely enough.
+    UnachievableBaudRate,
+}
+
+/// Computes the `AUX_MU_BAUD_REG` value for `baud` given a core clock of
+/// `clock` Hz. The mini UART runs at `clock / (8 * (divider + 1))` baud.
+fn divider(clock: u32, baud: u32) -> Result<u16, Error> {
+    if baud == 0 {
+        return Err(Error::UnachievableBaudRate);
+    }
+
+    let (clock, baud) = (clock as u64, baud as u64);
+    let divisor = (clock + 4 * baud) / (8 * baud);
+    if divisor == 0 || divisor > 0x10000 {
+        return Err(Error::UnachievableBaudRate);
+    }
+
+    let actual = clock / (8 * divisor);
+    let deviation = if actual > baud { actual - baud } else { baud - actual };
+    if deviation * 100 > baud * MAX_BAUD_ERROR_PERCENT {
+        return Err(Error::UnachievableBaudRate);
+    }
+
+    Ok((divisor - 1) as u16)
+}
+
+/// A builder for a `MiniUart` with a configuration other than the default of
+/// 115200 baud and 8 data bits.
+#[derive(Debug, Copy, Clone)]
+pub struct Builder {
+    clock: Option<u32>,
+    baud: u32,
+    data_bits: DataBits,
+}
+
+impl Builder {
+    /// Sets the frequency of the core clock in Hz. Defaults to the rate the
+    /// firmware reports, or 250MHz if it can't be queried.
+    ///
+    /// The mini UART's baud rate scales with the core clock, so the core
+    /// clock should be fixed (with `core_freq` in `config.txt`) for the baud
+    /// rate to stay put.
+    pub fn clock(mut self, hz: u32) -> Builder {
+        self.clock = Some(hz);
+        self
+    }
+
+    /// Sets the baud rate.
+    pub fn baud(mut self, baud: u32) -> Builder {
+        self.baud = baud;
+        self
+    }
+
+    /// Sets the number of data bits per character.
+    pub fn data_bits(mut self, data_bits: DataBits) -> Builder {
+        self.data_bits = data_bits;
+        self
+    }
+
     /// Initializes the mini UART by enabling it as an auxiliary peripheral,
-    /// setting the data size to 8 bits, setting the BAUD rate to ~115200 (baud
-    /// divider of 270), setting GPIO pins 14 and 15 to alternative function 5
-    /// (TXD1/RDXD1), and finally enabling the UART transmitter and receiver.
+    /// applying this configuration, setting GPIO pins 14 and 15 to
+    /// alternative function 5 (TXD1/RDXD1), and finally enabling the UART
+    /// transmitter and receiver.
     ///
-    /// By default, reads will never time out. To set a read timeout, use
-    /// `set_read_timeout()`.
-    pub fn new() -> MiniUart {
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableBaudRate` if the baud rate can't be
+    /// derived from the core clock to within 2%.
+    pub fn build(self) -> Result<MiniUart, Error> {
+        let clock = self.clock
+            .or_else(|| mailbox::clock_rate(Clock::Core))
+            .unwrap_or(CORE_CLOCK_HZ);
+        let divider = divider(clock, self.baud)?;
+
         Gpio::new(14).into_alt(Function::Alt5);
         Gpio::new(15).into_alt(Function::Alt5);
 
@@ -69,15 +153,108 @@ impl MiniUart {
             &mut *(MU_REG_BASE as *mut Registers)
         };
 
-        registers.AUX_MU_LCR_REG.or_mask(0b11); // UART 8-bit mode
-        registers.AUX_MU_BAUD_REG.write(270); // baudrate 115200
+        registers.AUX_MU_CNTL_REG.write(0); // disable while configuring
+        registers.AUX_MU_IER_REG.write(0);
+        registers.AUX_MU_LCR_REG.write(self.data_bits as u8);
+        registers.AUX_MU_MCR_REG.write(0);
+        registers.AUX_MU_BAUD_REG.write(divider);
 
-        registers.AUX_MU_CNTL_REG.or_mask(0b11); // enable transmitter and receiver
+        registers.AUX_MU_CNTL_REG.write(0b11); // enable transmitter and receiver
 
-            MiniUart {
-                registers: registers,
-                timeout: None,
-            }
+        Ok(MiniUart {
+            registers: registers,
+            clock,
+            timeout: None,
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            interrupts: false,
+        })
+    }
+}
+
+/// The Raspberry Pi's "mini UART".
+///
+/// Received bytes are buffered in software by `poll()`. Once interrupts are
+/// enabled with `enable_interrupts()`, bytes that are written are buffered
+/// too and sent from `poll()` as the transmit FIFO drains.
+pub struct MiniUart {
+    registers: &'static mut Registers,
+    clock: u32,
+    timeout: Option<u32>,
+    rx: RingBuffer,
+    tx: RingBuffer,
+    interrupts: bool,
+}
+
+impl MiniUart {
+    /// Returns a builder for a `MiniUart` with the default configuration.
+    pub fn builder() -> Builder {
+        Builder {
+            clock: None,
+            baud: 115200,
+            data_bits: DataBits::Eight,
+        }
+    }
+
+    /// Initializes the mini UART with the default configuration: 115200 baud
+    /// and 8 data bits. See `Builder::build()`.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    pub fn new() -> MiniUart {
+        MiniUart::builder().build().expect("default mini UART configuration")
+    }
+
+    /// Enables the mini UART's receive and transmit interrupts. `poll()`
+    /// must then be called whenever `Interrupt::Aux` is pending. Enabling
+    /// `Interrupt::Aux` in the interrupt controller is up to the caller.
+    pub fn enable_interrupts(&mut self) {
+        self.interrupts = true;
+        self.registers.AUX_MU_IER_REG.write(IER_RX | IER_REQUIRED);
+    }
+
+    /// Moves bytes from the receive FIFO into the receive buffer and from the
+    /// transmit buffer into the transmit FIFO. This is the mini UART's
+    /// interrupt handler, but it may be called at any time. Never blocks.
+    ///
+    /// Received bytes are dropped if the receive buffer is full.
+    pub fn poll(&mut self) {
+        while self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::DataReady as u8) {
+            let byte = self.registers.AUX_MU_IO_REG.read();
+            self.rx.push(byte);
+        }
+
+        while !self.tx.is_empty()
+            && self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::TxAvailable as u8)
+        {
+            let byte = self.tx.pop().unwrap();
+            self.registers.AUX_MU_IO_REG.write(byte);
+        }
+
+        if self.tx.is_empty() {
+            self.registers.AUX_MU_IER_REG.and_mask(!IER_TX);
+        }
+    }
+
+    /// Blocks until every buffered byte has been moved to the transmit FIFO.
+    pub fn flush(&mut self) {
+        while !self.tx.is_empty() {
+            self.poll();
+        }
+    }
+
+    /// Sets the baud rate to `baud`, to within 2%.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnachievableBaudRate` if the baud rate can't be
+    /// derived from the core clock closely enough. The baud rate is then
+    /// unchanged.
+    pub fn set_baud_rate(&mut self, baud: u32) -> Result<(), Error> {
+        let divider = divider(self.clock, baud)?;
+        self.flush();
+        self.registers.AUX_MU_BAUD_REG.write(divider);
+        Ok(())
     }
 
     /// Set the read timeout to `milliseconds` milliseconds.
@@ -85,9 +262,19 @@ impl MiniUart {
         self.timeout = Some(milliseconds);
     }
 
-    /// Write the byte `byte`. This method blocks until there is space available
-    /// in the output FIFO.
+    /// Write the byte `byte`. If interrupts are enabled, the byte is buffered
+    /// and this method only blocks while the transmit buffer is full.
+    /// Otherwise, this method blocks until there is space available in the
+    /// output FIFO.
     pub fn write_byte(&mut self, byte: u8) {
+        if self.interrupts {
+            while !self.tx.push(byte) {
+                self.poll();
+            }
+            self.registers.AUX_MU_IER_REG.or_mask(IER_TX);
+            return;
+        }
+
         while !self.registers.AUX_MU_LSR_REG
             .has_mask(LsrStatus::TxAvailable as u8) { };
         self.registers.AUX_MU_IO_REG.write(byte);
@@ -97,7 +284,14 @@ impl MiniUart {
     /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
     /// return immediately. This method does not block.
     pub fn has_byte(&self) -> bool {
-        self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::DataReady as u8)
+        !self.rx.is_empty()
+            || self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::DataReady as u8)
+    }
+
+    /// Reads a byte if one is ready. Never blocks.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        self.poll();
+        self.rx.pop()
     }
 
     /// Blocks until there is a byte ready to read. If a read timeout is set,
@@ -128,8 +322,11 @@ impl MiniUart {
 
     /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
     pub fn read_byte(&mut self) -> u8 {
-        while !self.has_byte() { };
-        self.registers.AUX_MU_IO_REG.read()
+        loop {
+            if let Some(byte) = self.try_read_byte() {
+                return byte;
+            }
+        }
     }
 }
 
@@ -186,6 +383,7 @@ mod uart_io {
         }
 
         fn flush(&mut self) -> io::Result<()> {
+            MiniUart::flush(self);
             Ok(())
         }
     }